@@ -0,0 +1,388 @@
+//! Verification of `packed` WebAuthn attestation
+//!
+//! An attestation object is the CBOR map `{fmt, attStmt, authData}` returned when a
+//! passkey is created. In the `packed` format the statement holds an ES256 signature
+//! over `authData || sha256(clientDataJSON)`, made either by the new credential itself
+//! (self attestation) or by the attestation certificate first in `x5c` (basic
+//! attestation). The certificate must be signed by the issuer key trusted for the
+//! AAGUID in the authenticator data, which names the authenticator model; the rest of
+//! `x5c` is not used.
+
+use soroban_sdk::{Bytes, BytesN, Env};
+
+use crate::authenticator_data::{self, AttestedCredential};
+use crate::cbor::{self, Decoder};
+use crate::der::{self, Reader, TAG_BIT_STRING, TAG_BOOLEAN, TAG_INTEGER, TAG_OCTET_STRING};
+use crate::der::{TAG_OID, TAG_SEQUENCE};
+use crate::public_key;
+
+/// Maximum accepted size of an attestation object
+pub const MAX_ATTESTATION_LEN: usize = 4096;
+
+/// COSE algorithm identifier of ECDSA with P-256 and SHA-256
+const COSE_ALG_ES256: i64 = -7;
+
+/// ecdsa-with-SHA256 (1.2.840.10045.4.3.2)
+const OID_ECDSA_SHA256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02];
+/// id-ecPublicKey (1.2.840.10045.2.1)
+const OID_EC_PUBLIC_KEY: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01];
+/// prime256v1 (1.2.840.10045.3.1.7)
+const OID_P256: &[u8] = &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07];
+/// basicConstraints (2.5.29.19)
+const OID_BASIC_CONSTRAINTS: &[u8] = &[0x55, 0x1d, 0x13];
+/// id-fido-gen-ce-aaguid (1.3.6.1.4.1.45724.1.1.4)
+const OID_FIDO_AAGUID: &[u8] = &[
+    0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xe5, 0x1c, 0x01, 0x01, 0x04,
+];
+
+/// Context-specific tags in a certificate's TBSCertificate
+const TAG_VERSION: u8 = 0xa0;
+const TAG_ISSUER_UNIQUE_ID: u8 = 0x81;
+const TAG_SUBJECT_UNIQUE_ID: u8 = 0x82;
+const TAG_EXTENSIONS: u8 = 0xa3;
+
+/// Reasons an attestation can be rejected
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum AttestationError {
+    /// The attestation object or its authenticator data is malformed
+    Malformed,
+    /// The format is not `packed`, or the algorithm is not ES256
+    Unsupported,
+    /// The attestation certificate does not meet the `packed` requirements
+    InvalidCertificate,
+    /// No issuer is trusted for the authenticator model of a basic attestation
+    UntrustedIssuer,
+    /// The credential public key is not a valid P-256 point
+    InvalidPublicKey,
+    /// The signature is out of range
+    InvalidSignature,
+}
+
+impl From<cbor::Malformed> for AttestationError {
+    fn from(_: cbor::Malformed) -> Self {
+        AttestationError::Malformed
+    }
+}
+
+/// A verified attestation
+#[derive(Copy, Clone, Debug)]
+pub struct Attestation<'a> {
+    pub authenticator_data: &'a [u8],
+    pub credential: AttestedCredential<'a>,
+    /// Public key of the credential (X || Y)
+    pub public_key: [u8; 64],
+    /// Whether the credential signed its own attestation
+    pub self_attested: bool,
+}
+
+/// The members of a `packed` attestation object
+struct AttestationObject<'a> {
+    alg: i64,
+    sig: &'a [u8],
+    /// The attestation certificate, for basic attestation
+    certificate: Option<&'a [u8]>,
+    authenticator_data: &'a [u8],
+}
+
+/// What a `packed` attestation certificate vouches for, and its issuer's signature
+struct Certificate<'a> {
+    public_key: [u8; 64],
+    aaguid: Option<[u8; 16]>,
+    /// The signed TBSCertificate, header included
+    tbs: &'a [u8],
+    signature: [u8; 64],
+}
+
+/// Verifies a `packed` attestation for the clientDataJSON with the given hash
+///
+/// `issuer` gives the public key (X || Y) trusted to sign the attestation certificates
+/// of an authenticator model, if any.
+pub fn verify<'a>(
+    env: &Env,
+    attestation_object: &'a [u8],
+    client_data_hash: &[u8; 32],
+    issuer: impl Fn(&[u8; 16]) -> Option<[u8; 64]>,
+) -> Result<Attestation<'a>, AttestationError> {
+    let object = parse_object(attestation_object)?;
+    if object.alg != COSE_ALG_ES256 {
+        return Err(AttestationError::Unsupported);
+    }
+
+    let credential = authenticator_data::parse_attested_credential(object.authenticator_data)
+        .map_err(|_| AttestationError::Malformed)?;
+    let public_key =
+        public_key::parse(credential.public_key).map_err(|_| AttestationError::InvalidPublicKey)?;
+
+    // Basic attestation is signed by the certificate, self attestation by the credential
+    let signer = match object.certificate {
+        Some(certificate) => {
+            let certificate = parse_certificate(certificate)?;
+            if certificate
+                .aaguid
+                .is_some_and(|aaguid| aaguid != credential.aaguid)
+            {
+                return Err(AttestationError::InvalidCertificate);
+            }
+            let issuer = issuer(&credential.aaguid).ok_or(AttestationError::UntrustedIssuer)?;
+            verify_signature(
+                env,
+                &issuer,
+                Bytes::from_slice(env, certificate.tbs),
+                certificate.signature,
+            )?;
+            certificate.public_key
+        }
+        None => public_key,
+    };
+
+    let signature =
+        der::ecdsa_signature(object.sig).map_err(|_| AttestationError::InvalidSignature)?;
+    let mut message = Bytes::from_slice(env, object.authenticator_data);
+    message.extend_from_array(client_data_hash);
+    verify_signature(env, &signer, message, signature)?;
+
+    Ok(Attestation {
+        authenticator_data: object.authenticator_data,
+        credential,
+        public_key,
+        self_attested: object.certificate.is_none(),
+    })
+}
+
+fn parse_object(input: &[u8]) -> Result<AttestationObject<'_>, AttestationError> {
+    let mut decoder = Decoder::new(input);
+    let (mut fmt, mut statement, mut authenticator_data) = (None, None, None);
+
+    for _ in 0..decoder.map()? {
+        let key = decoder.text()?;
+        let start = decoder.position();
+        decoder.skip()?;
+        let value = &input[start..decoder.position()];
+
+        let slot = match key {
+            b"fmt" => &mut fmt,
+            b"attStmt" => &mut statement,
+            b"authData" => &mut authenticator_data,
+            _ => continue,
+        };
+        if slot.replace(value).is_some() {
+            return Err(AttestationError::Malformed);
+        }
+    }
+    if !decoder.is_empty() {
+        return Err(AttestationError::Malformed);
+    }
+
+    let (Some(fmt), Some(statement), Some(authenticator_data)) =
+        (fmt, statement, authenticator_data)
+    else {
+        return Err(AttestationError::Malformed);
+    };
+    if Decoder::new(fmt).text()? != b"packed" {
+        return Err(AttestationError::Unsupported);
+    }
+    let authenticator_data = Decoder::new(authenticator_data).bytes()?;
+
+    let (mut alg, mut sig, mut certificate) = (None, None, None);
+    let mut decoder = Decoder::new(statement);
+    for _ in 0..decoder.map()? {
+        match decoder.text()? {
+            b"alg" => alg = Some(decoder.int()?),
+            b"sig" => sig = Some(decoder.bytes()?),
+            b"x5c" => {
+                let len = decoder.array()?;
+                if len == 0 {
+                    return Err(AttestationError::Malformed);
+                }
+                certificate = Some(decoder.bytes()?);
+                // Intermediate certificates are not used
+                for _ in 1..len {
+                    decoder.skip()?;
+                }
+            }
+            _ => decoder.skip()?,
+        }
+    }
+
+    match (alg, sig) {
+        (Some(alg), Some(sig)) => Ok(AttestationObject {
+            alg,
+            sig,
+            certificate,
+            authenticator_data,
+        }),
+        _ => Err(AttestationError::Malformed),
+    }
+}
+
+/// Verifies an ES256 signature over `message` by `public_key` (X || Y)
+fn verify_signature(
+    env: &Env,
+    public_key: &[u8; 64],
+    message: Bytes,
+    mut signature: [u8; 64],
+) -> Result<(), AttestationError> {
+    normalize_s(&mut signature);
+    if !crate::is_valid_signature_encoding(&signature) {
+        return Err(AttestationError::InvalidSignature);
+    }
+
+    env.crypto().secp256r1_verify(
+        &crate::sec1_uncompressed(env, &BytesN::from_array(env, public_key)),
+        &env.crypto().sha256(&message),
+        &BytesN::from_array(env, &signature),
+    );
+    Ok(())
+}
+
+/// Reads the public key, AAGUID and issuer signature of an attestation certificate,
+/// checking it is an X.509 v3 end-entity certificate for a P-256 key signed with ES256
+fn parse_certificate(der: &[u8]) -> Result<Certificate<'_>, AttestationError> {
+    read_certificate(der).map_err(|_| AttestationError::InvalidCertificate)
+}
+
+fn read_certificate(der: &[u8]) -> Result<Certificate<'_>, der::Malformed> {
+    let mut outer = Reader::new(der);
+    let mut certificate = Reader::new(outer.read(TAG_SEQUENCE)?);
+    if !outer.is_empty() {
+        return Err(der::Malformed);
+    }
+
+    let signed = certificate.remaining();
+    let tbs_contents = certificate.read(TAG_SEQUENCE)?;
+    let tbs_der = &signed[..signed.len() - certificate.remaining().len()];
+    let mut algorithm = Reader::new(certificate.read(TAG_SEQUENCE)?);
+    if algorithm.read(TAG_OID)? != OID_ECDSA_SHA256 || !algorithm.is_empty() {
+        return Err(der::Malformed);
+    }
+    let signature = match certificate.read(TAG_BIT_STRING)? {
+        [0x00, signature @ ..] => der::ecdsa_signature(signature)?,
+        _ => return Err(der::Malformed),
+    };
+    if !certificate.is_empty() {
+        return Err(der::Malformed);
+    }
+
+    let mut tbs = Reader::new(tbs_contents);
+
+    // Version 3 (encoded as 2)
+    if Reader::new(tbs.read(TAG_VERSION)?).read(TAG_INTEGER)? != [0x02] {
+        return Err(der::Malformed);
+    }
+    tbs.read(TAG_INTEGER)?; // serialNumber
+    tbs.read(TAG_SEQUENCE)?; // signature
+    tbs.read(TAG_SEQUENCE)?; // issuer
+    tbs.read(TAG_SEQUENCE)?; // validity
+    tbs.read(TAG_SEQUENCE)?; // subject
+
+    let mut key_info = Reader::new(tbs.read(TAG_SEQUENCE)?);
+    let mut algorithm = Reader::new(key_info.read(TAG_SEQUENCE)?);
+    if algorithm.read(TAG_OID)? != OID_EC_PUBLIC_KEY || algorithm.read(TAG_OID)? != OID_P256 {
+        return Err(der::Malformed);
+    }
+    let public_key = match key_info.read(TAG_BIT_STRING)? {
+        [0x00, point @ ..] => public_key::parse(point).map_err(|_| der::Malformed)?,
+        _ => return Err(der::Malformed),
+    };
+
+    tbs.read_optional(TAG_ISSUER_UNIQUE_ID)?;
+    tbs.read_optional(TAG_SUBJECT_UNIQUE_ID)?;
+    let mut extensions = Reader::new(tbs.read(TAG_EXTENSIONS)?);
+    let mut extensions = Reader::new(extensions.read(TAG_SEQUENCE)?);
+
+    // The Basic Constraints extension must be present and not allow signing certificates
+    let mut is_end_entity = false;
+    let mut aaguid = None;
+    while !extensions.is_empty() {
+        let mut extension = Reader::new(extensions.read(TAG_SEQUENCE)?);
+        let id = extension.read(TAG_OID)?;
+        extension.read_optional(TAG_BOOLEAN)?; // critical
+        let mut value = Reader::new(extension.read(TAG_OCTET_STRING)?);
+
+        if id == OID_BASIC_CONSTRAINTS {
+            let mut constraints = Reader::new(value.read(TAG_SEQUENCE)?);
+            is_end_entity = !matches!(constraints.read_optional(TAG_BOOLEAN)?, Some([0xff]));
+        } else if id == OID_FIDO_AAGUID {
+            let mut id = [0u8; 16];
+            match value.read(TAG_OCTET_STRING)? {
+                value if value.len() == 16 => id.copy_from_slice(value),
+                _ => return Err(der::Malformed),
+            }
+            aaguid = Some(id);
+        }
+    }
+    if !is_end_entity {
+        return Err(der::Malformed);
+    }
+
+    Ok(Certificate {
+        public_key,
+        aaguid,
+        tbs: tbs_der,
+        signature,
+    })
+}
+
+/// Replaces a high `s` with `n - s`, which the host requires and is equally valid
+fn normalize_s(signature: &mut [u8; 64]) {
+    if signature[32..] <= crate::SECP256R1_HALF_ORDER[..] {
+        return;
+    }
+
+    let mut borrow = 0u16;
+    for i in (0..32).rev() {
+        let difference = u16::from(crate::SECP256R1_ORDER[i])
+            .wrapping_sub(u16::from(signature[32 + i]))
+            .wrapping_sub(borrow);
+        signature[32 + i] = difference as u8;
+        borrow = (difference >> 8) & 1;
+    }
+}
+
+#[cfg(test)]
+mod attestation_test {
+    use super::*;
+
+    #[test]
+    fn test_normalize_s() {
+        // s = n - 1 becomes 1
+        let mut signature = [0u8; 64];
+        signature[..32].copy_from_slice(&[0x01; 32]);
+        signature[32..].copy_from_slice(&crate::SECP256R1_ORDER);
+        signature[63] -= 1;
+        normalize_s(&mut signature);
+        let mut one = [0u8; 32];
+        one[31] = 1;
+        assert_eq!(signature[..32], [0x01; 32]);
+        assert_eq!(signature[32..], one);
+
+        // A low s is kept
+        normalize_s(&mut signature);
+        assert_eq!(signature[32..], one);
+    }
+
+    #[test]
+    fn test_rejects_malformed_objects() {
+        let env = Env::default();
+        let hash = [0u8; 32];
+
+        // Not a map, an empty map, and a `none` attestation
+        assert_eq!(
+            verify(&env, &[0x80], &hash, |_| None).unwrap_err(),
+            AttestationError::Malformed
+        );
+        assert_eq!(
+            verify(&env, &[0xa0], &hash, |_| None).unwrap_err(),
+            AttestationError::Malformed
+        );
+        let none = [
+            0xa3, 0x63, b'f', b'm', b't', 0x64, b'n', b'o', b'n', b'e', 0x67, b'a', b't', b't',
+            b'S', b't', b'm', b't', 0xa0, 0x68, b'a', b'u', b't', b'h', b'D', b'a', b't', b'a',
+            0x40,
+        ];
+        assert_eq!(
+            verify(&env, &none, &hash, |_| None).unwrap_err(),
+            AttestationError::Unsupported
+        );
+    }
+}
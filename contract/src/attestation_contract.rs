@@ -0,0 +1,46 @@
+//! Interface of the attestation verifier contract
+//!
+//! Verifying a `packed` attestation means decoding CBOR and X.509 certificates, which
+//! would not fit in the account's wasm next to everything else. The account instead
+//! calls the verifier named by its attestation configuration, built from this crate
+//! with the `attestation` feature, and keeps the checks that depend on its own state:
+//! clientDataJSON, the relying party and flags, and which authenticators it allows.
+
+use soroban_sdk::{contractclient, contracttype, Bytes, BytesN, Env, Map};
+
+use crate::Error;
+
+/// A `packed` attestation whose signatures verified
+// Only the verifier's contract spec describes it, since the account merely reads it
+#[cfg_attr(feature = "attestation", contracttype)]
+#[cfg_attr(not(feature = "attestation"), contracttype(export = false))]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VerifiedAttestation {
+    /// WebAuthn credential ID of the new passkey
+    pub credential_id: Bytes,
+    /// Public key of the new passkey (X || Y)
+    pub public_key: BytesN<64>,
+    /// AAGUID of the authenticator model that created the passkey
+    pub aaguid: BytesN<16>,
+    /// Authenticator data the attestation signed
+    pub authenticator_data: Bytes,
+    /// Whether the passkey signed its own attestation
+    pub self_attested: bool,
+}
+
+/// Entry point of the attestation verifier contract
+#[contractclient(name = "AttestationContractClient")]
+pub trait AttestationContract {
+    /// Verifies a `packed` attestation for the clientDataJSON with the given hash
+    ///
+    /// `issuers` gives the public key (X || Y) trusted to sign the attestation
+    /// certificates of each authenticator model; basic attestation by other models is
+    /// rejected with `AttestationNotAllowed`, malformed or unsupported attestation with
+    /// `InvalidAttestation`.
+    fn verify(
+        env: Env,
+        attestation_object: Bytes,
+        client_data_hash: BytesN<32>,
+        issuers: Map<BytesN<16>, BytesN<64>>,
+    ) -> Result<VerifiedAttestation, Error>;
+}
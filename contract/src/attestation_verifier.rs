@@ -0,0 +1,55 @@
+//! Attestation verifier contract, compiled as its own wasm with the `attestation` feature
+//!
+//! Accounts name its address in their attestation configuration and call `verify` when
+//! an attested passkey is registered. It keeps no state, so one deployment can serve
+//! every account.
+
+use soroban_sdk::{contract, contractimpl, Bytes, BytesN, Env, Map};
+
+use crate::attestation::{self, AttestationError};
+use crate::attestation_contract::{AttestationContract, VerifiedAttestation};
+use crate::{load, Error};
+
+#[contract]
+pub struct AttestationVerifier;
+
+impl From<AttestationError> for Error {
+    fn from(error: AttestationError) -> Self {
+        match error {
+            AttestationError::InvalidPublicKey => Error::InvalidPublicKey,
+            AttestationError::InvalidSignature => Error::InvalidSignature,
+            AttestationError::UntrustedIssuer => Error::AttestationNotAllowed,
+            _ => Error::InvalidAttestation,
+        }
+    }
+}
+
+#[contractimpl]
+impl AttestationContract for AttestationVerifier {
+    fn verify(
+        env: Env,
+        attestation_object: Bytes,
+        client_data_hash: BytesN<32>,
+        issuers: Map<BytesN<16>, BytesN<64>>,
+    ) -> Result<VerifiedAttestation, Error> {
+        let mut buffer = [0u8; attestation::MAX_ATTESTATION_LEN];
+        let attestation = attestation::verify(
+            &env,
+            load(&attestation_object, &mut buffer).ok_or(Error::InvalidAttestation)?,
+            &client_data_hash.to_array(),
+            |aaguid| {
+                issuers
+                    .get(BytesN::from_array(&env, aaguid))
+                    .map(|issuer| issuer.to_array())
+            },
+        )?;
+
+        Ok(VerifiedAttestation {
+            credential_id: Bytes::from_slice(&env, attestation.credential.credential_id),
+            public_key: BytesN::from_array(&env, &attestation.public_key),
+            aaguid: BytesN::from_array(&env, &attestation.credential.aaguid),
+            authenticator_data: Bytes::from_slice(&env, attestation.authenticator_data),
+            self_attested: attestation.self_attested,
+        })
+    }
+}
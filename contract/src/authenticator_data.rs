@@ -0,0 +1,216 @@
+//! Parsing of WebAuthn authenticator data
+//!
+//! Layout: `rpIdHash (32) || flags (1) || signCount (4, big-endian)`, optionally
+//! followed by attested credential data (at registration) and extensions. Assertions
+//! only need the fixed header; the attested credential data is parsed from
+//! attestation objects.
+
+use soroban_sdk::Bytes;
+
+#[cfg(any(test, feature = "attestation"))]
+use crate::cbor::Decoder;
+
+/// Length of the fixed authenticator data header
+pub const HEADER_LEN: u32 = 37;
+
+/// User Present flag
+pub const FLAG_UP: u8 = 0x01;
+
+/// User Verified flag (biometric or PIN)
+pub const FLAG_UV: u8 = 0x04;
+
+/// Backup Eligibility flag: the credential can be synced to other devices
+pub const FLAG_BE: u8 = 0x08;
+
+/// Backup State flag: the credential is currently backed up (synced)
+pub const FLAG_BS: u8 = 0x10;
+
+/// Attested credential data included flag
+#[cfg(any(test, feature = "attestation"))]
+pub const FLAG_AT: u8 = 0x40;
+
+/// The fixed header of authenticator data
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AuthenticatorData {
+    pub rp_id_hash: [u8; 32],
+    pub flags: u8,
+    pub sign_count: u32,
+}
+
+/// Authenticator data is shorter than its fixed header
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct TooShort;
+
+/// The credential created at registration, as attested by the authenticator
+#[cfg(any(test, feature = "attestation"))]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct AttestedCredential<'a> {
+    /// Model of the authenticator
+    pub aaguid: [u8; 16],
+    pub credential_id: &'a [u8],
+    /// COSE_Key of the credential
+    pub public_key: &'a [u8],
+}
+
+/// The attested credential data is missing or malformed
+#[cfg(any(test, feature = "attestation"))]
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct InvalidCredentialData;
+
+/// Parses the authenticator data header
+pub fn parse(authenticator_data: &Bytes) -> Result<AuthenticatorData, TooShort> {
+    if authenticator_data.len() < HEADER_LEN {
+        return Err(TooShort);
+    }
+
+    let mut header = [0u8; HEADER_LEN as usize];
+    authenticator_data
+        .slice(..HEADER_LEN)
+        .copy_into_slice(&mut header);
+
+    let mut rp_id_hash = [0u8; 32];
+    rp_id_hash.copy_from_slice(&header[..32]);
+
+    Ok(AuthenticatorData {
+        rp_id_hash,
+        flags: header[32],
+        sign_count: u32::from_be_bytes([header[33], header[34], header[35], header[36]]),
+    })
+}
+
+/// Parses the attested credential data following the header
+///
+/// `aaguid (16) || credentialIdLength (2, big-endian) || credentialId ||
+/// credentialPublicKey (COSE_Key)`; extensions may follow.
+#[cfg(any(test, feature = "attestation"))]
+pub fn parse_attested_credential(
+    authenticator_data: &[u8],
+) -> Result<AttestedCredential<'_>, InvalidCredentialData> {
+    let header_len = HEADER_LEN as usize;
+    if authenticator_data.len() < header_len + 18 || authenticator_data[32] & FLAG_AT == 0 {
+        return Err(InvalidCredentialData);
+    }
+
+    let data = &authenticator_data[header_len..];
+    let mut aaguid = [0u8; 16];
+    aaguid.copy_from_slice(&data[..16]);
+    let id_len = usize::from(u16::from_be_bytes([data[16], data[17]]));
+    let credential_id = data.get(18..18 + id_len).ok_or(InvalidCredentialData)?;
+
+    // The COSE_Key is the single CBOR item after the credential ID
+    let rest = &data[18 + id_len..];
+    let mut decoder = Decoder::new(rest);
+    decoder.skip().map_err(|_| InvalidCredentialData)?;
+
+    Ok(AttestedCredential {
+        aaguid,
+        credential_id,
+        public_key: &rest[..decoder.position()],
+    })
+}
+
+impl AuthenticatorData {
+    /// Whether the user was present (e.g. touched the authenticator)
+    pub fn user_present(&self) -> bool {
+        self.flags & FLAG_UP != 0
+    }
+
+    /// Whether the user was verified (biometric or PIN)
+    pub fn user_verified(&self) -> bool {
+        self.flags & FLAG_UV != 0
+    }
+
+    /// Whether the credential can be synced, e.g. through a cloud keychain
+    ///
+    /// Set for the credential's whole lifetime; unset means it is bound to one device.
+    pub fn backup_eligible(&self) -> bool {
+        self.flags & FLAG_BE != 0
+    }
+
+    /// Whether the credential is currently backed up
+    pub fn backed_up(&self) -> bool {
+        self.flags & FLAG_BS != 0
+    }
+}
+
+#[cfg(test)]
+mod authenticator_data_test {
+    use super::*;
+    use soroban_sdk::Env;
+
+    #[test]
+    fn test_parse_header() {
+        let env = Env::default();
+        let mut data = [0u8; 40];
+        data[..32].copy_from_slice(&[0xab; 32]);
+        data[32] = FLAG_UP | FLAG_UV;
+        data[33..37].copy_from_slice(&[0x01, 0x02, 0x03, 0x04]);
+
+        let parsed = parse(&Bytes::from_slice(&env, &data)).unwrap();
+        assert_eq!(parsed.rp_id_hash, [0xab; 32]);
+        assert_eq!(parsed.sign_count, 0x01020304);
+        assert!(parsed.user_present());
+        assert!(parsed.user_verified());
+    }
+
+    #[test]
+    fn test_parse_flags() {
+        let env = Env::default();
+        let mut data = [0u8; 37];
+        data[32] = FLAG_UP;
+
+        let parsed = parse(&Bytes::from_slice(&env, &data)).unwrap();
+        assert!(parsed.user_present());
+        assert!(!parsed.user_verified());
+        assert!(!parsed.backup_eligible());
+        assert!(!parsed.backed_up());
+
+        data[32] = FLAG_UP | FLAG_BE | FLAG_BS;
+        let parsed = parse(&Bytes::from_slice(&env, &data)).unwrap();
+        assert!(parsed.backup_eligible());
+        assert!(parsed.backed_up());
+    }
+
+    #[test]
+    fn test_parse_attested_credential() {
+        // Header with UP and AT, AAGUID, a 2-byte credential ID, a 3-byte "key" and
+        // an extensions map
+        let mut data = [0u8; 37 + 16 + 2 + 2 + 3 + 1];
+        data[32] = FLAG_UP | FLAG_AT;
+        data[37..53].copy_from_slice(&[0x11; 16]);
+        data[53..55].copy_from_slice(&[0x00, 0x02]);
+        data[55..57].copy_from_slice(&[0xaa, 0xbb]);
+        data[57..60].copy_from_slice(&[0xa1, 0x01, 0x02]);
+        data[60] = 0xa0;
+
+        let credential = parse_attested_credential(&data).unwrap();
+        assert_eq!(credential.aaguid, [0x11; 16]);
+        assert_eq!(credential.credential_id, &[0xaa, 0xbb]);
+        assert_eq!(credential.public_key, &[0xa1, 0x01, 0x02]);
+
+        // Without the AT flag, or with a credential ID running past the end
+        let mut without_flag = data;
+        without_flag[32] = FLAG_UP;
+        assert_eq!(
+            parse_attested_credential(&without_flag),
+            Err(InvalidCredentialData)
+        );
+        let mut too_long = data;
+        too_long[54] = 0x10;
+        assert_eq!(
+            parse_attested_credential(&too_long),
+            Err(InvalidCredentialData)
+        );
+        assert_eq!(
+            parse_attested_credential(&data[..58]),
+            Err(InvalidCredentialData)
+        );
+    }
+
+    #[test]
+    fn test_parse_too_short() {
+        let env = Env::default();
+        let data = Bytes::from_slice(&env, &[0u8; 36]);
+        assert_eq!(parse(&data), Err(TooShort));
+    }
+}
@@ -14,55 +14,230 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use soroban_sdk::{Bytes, Env};
+//! Allocation-free, unpadded base64url (RFC 4648 §5) as used by WebAuthn challenges
+//!
+//! Decoding is strict: padding, characters outside the URL-safe alphabet and set
+//! trailing bits are rejected, so every byte string has exactly one accepted encoding.
+//! The account decodes the challenges it receives; clients encode the ones they sign.
 
-const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
+/// The URL-safe alphabet
+pub const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789-_";
 
-/// Decode base64url bytes in Soroban environment
-pub fn decode(env: &Env, base64_url: &Bytes) -> Result<Bytes, &'static str> {
-    let mut output = Bytes::new(env);
-    let input = base64_url.to_vec();
-    
-    if input.is_empty() {
-        return Ok(output);
+/// Why base64url input was rejected
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// The length leaves a single character in the last group, which cannot encode a byte
+    InvalidLength,
+    /// A character is outside the URL-safe alphabet (including `=` padding)
+    InvalidCharacter,
+    /// The last character has bits set beyond the encoded bytes
+    NonCanonical,
+    /// The output buffer cannot hold the decoded bytes
+    BufferTooSmall,
+}
+
+/// Length of the encoding of `len` bytes
+pub const fn encoded_len(len: usize) -> usize {
+    len / 3 * 4 + [0, 2, 3][len % 3]
+}
+
+/// Length of the bytes `len` characters decode to
+pub fn decoded_len(len: usize) -> Result<usize, DecodeError> {
+    match len % 4 {
+        1 => Err(DecodeError::InvalidLength),
+        rest => Ok(len / 4 * 3 + rest.saturating_sub(1)),
     }
+}
+
+/// Encodes `input` into the start of `output` and returns the encoding
+///
+/// # Panics
+/// If `output` is shorter than [`encoded_len`] of the input.
+pub fn encode_into<'a>(input: &[u8], output: &'a mut [u8]) -> &'a [u8] {
+    let output = &mut output[..encoded_len(input.len())];
+    for (chunk, encoded) in input.chunks(3).zip(output.chunks_mut(4)) {
+        let mut group = [0u8; 4];
+        group[1..=chunk.len()].copy_from_slice(chunk);
+        let group = u32::from_be_bytes(group);
 
-    // Remove padding
-    let input_len = input.len();
-    let mut actual_len = input_len;
-    
-    // Base64url doesn't use padding, but handle it if present
-    while actual_len > 0 && input.get(actual_len - 1) == Some(&(b'=' as u32)) {
-        actual_len -= 1;
+        for (i, character) in encoded.iter_mut().enumerate() {
+            *character = ALPHABET[(group >> (18 - 6 * i) & 0x3f) as usize];
+        }
     }
+    output
+}
+
+/// Decodes `input` into the start of `output` and returns the decoded bytes
+pub fn decode_into<'a>(input: &[u8], output: &'a mut [u8]) -> Result<&'a [u8], DecodeError> {
+    let len = decoded_len(input.len())?;
+    let output = output.get_mut(..len).ok_or(DecodeError::BufferTooSmall)?;
+
+    for (chunk, decoded) in input.chunks(4).zip(output.chunks_mut(3)) {
+        let mut group = 0u32;
+        for &character in chunk {
+            group = group << 6 | u32::from(decode_char(character)?);
+        }
+        // Align a short last group as if it were complete
+        let group = (group << (6 * (4 - chunk.len()))).to_be_bytes();
 
-    let mut acc = 0u32;
-    let mut acc_len = 0u32;
-    
-    for i in 0..actual_len {
-        let byte = input.get(i).ok_or("Invalid input")? as u8;
-        let val = decode_char(byte)?;
-        
-        acc = (acc << 6) | (val as u32);
-        acc_len += 6;
-        
-        if acc_len >= 8 {
-            acc_len -= 8;
-            let byte_val = (acc >> acc_len) & 0xFF;
-            output.push_back(byte_val);
+        decoded.copy_from_slice(&group[1..=decoded.len()]);
+        if group[decoded.len() + 1..].iter().any(|&bits| bits != 0) {
+            return Err(DecodeError::NonCanonical);
         }
     }
-    
     Ok(output)
 }
 
-fn decode_char(byte: u8) -> Result<u8, &'static str> {
-    match byte {
-        b'A'..=b'Z' => Ok(byte - b'A'),
-        b'a'..=b'z' => Ok(byte - b'a' + 26),
-        b'0'..=b'9' => Ok(byte - b'0' + 52),
+/// Encodes a 32-byte value, e.g. a signature payload, into its 43 characters
+pub fn encode_32(input: &[u8; 32]) -> [u8; 43] {
+    let mut output = [0u8; 43];
+    encode_into(input, &mut output);
+    output
+}
+
+/// Decodes exactly 32 bytes, e.g. a signature payload, from 43 characters
+pub fn decode_32(input: &[u8]) -> Result<[u8; 32], DecodeError> {
+    if input.len() != encoded_len(32) {
+        return Err(DecodeError::InvalidLength);
+    }
+    let mut output = [0u8; 32];
+    decode_into(input, &mut output)?;
+    Ok(output)
+}
+
+fn decode_char(character: u8) -> Result<u8, DecodeError> {
+    match character {
+        b'A'..=b'Z' => Ok(character - b'A'),
+        b'a'..=b'z' => Ok(character - b'a' + 26),
+        b'0'..=b'9' => Ok(character - b'0' + 52),
         b'-' => Ok(62),
         b'_' => Ok(63),
-        _ => Err("Invalid base64url character"),
+        _ => Err(DecodeError::InvalidCharacter),
+    }
+}
+
+#[cfg(test)]
+mod base64_url_test {
+    extern crate std;
+
+    use super::*;
+    use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+    use proptest::prelude::*;
+    use std::vec::Vec;
+
+    fn encode(input: &[u8]) -> Vec<u8> {
+        let mut output = std::vec![0u8; encoded_len(input.len())];
+        encode_into(input, &mut output);
+        output
+    }
+
+    fn decode(input: &[u8]) -> Result<Vec<u8>, DecodeError> {
+        let mut output = std::vec![0u8; input.len()];
+        decode_into(input, &mut output).map(<[u8]>::to_vec)
+    }
+
+    /// Checks the codec agrees with the `base64` crate, which is also strict
+    fn assert_matches_reference(input: &[u8]) {
+        assert_eq!(
+            decode(input).ok(),
+            URL_SAFE_NO_PAD.decode(input).ok(),
+            "{:?}",
+            std::string::String::from_utf8_lossy(input)
+        );
+    }
+
+    #[test]
+    fn test_rfc_4648_vectors() {
+        for (decoded, encoded) in [
+            (&b""[..], &b""[..]),
+            (b"f", b"Zg"),
+            (b"fo", b"Zm8"),
+            (b"foo", b"Zm9v"),
+            (b"foob", b"Zm9vYg"),
+            (b"fooba", b"Zm9vYmE"),
+            (b"foobar", b"Zm9vYmFy"),
+            (&[0xfb, 0xff], b"-_8"),
+        ] {
+            assert_eq!(encode(decoded), encoded);
+            assert_eq!(decode(encoded).unwrap(), decoded);
+        }
+    }
+
+    #[test]
+    fn test_rejects_non_canonical_input() {
+        assert_eq!(decode(b"Zm9vYg=="), Err(DecodeError::InvalidCharacter));
+        assert_eq!(decode(b"Zm9v+/"), Err(DecodeError::InvalidCharacter));
+        assert_eq!(decode(b"Zm9vY"), Err(DecodeError::InvalidLength));
+        // `Zh` and `Zm-` only differ from `Zg` and `Zm8` in their unused bits
+        assert_eq!(decode(b"Zh"), Err(DecodeError::NonCanonical));
+        assert_eq!(decode(b"Zm-"), Err(DecodeError::NonCanonical));
+
+        let mut output = [0u8; 2];
+        assert_eq!(
+            decode_into(b"Zm9v", &mut output),
+            Err(DecodeError::BufferTooSmall)
+        );
+    }
+
+    #[test]
+    fn test_32_byte_fast_path() {
+        let payload = [0xa5u8; 32];
+        let encoded = encode_32(&payload);
+        assert_eq!(&encoded[..], URL_SAFE_NO_PAD.encode(payload).as_bytes());
+        assert_eq!(decode_32(&encoded), Ok(payload));
+
+        assert_eq!(decode_32(&encoded[..42]), Err(DecodeError::InvalidLength));
+        assert_eq!(decode_32(b"Zm9v"), Err(DecodeError::InvalidLength));
+        let mut last_bits = encoded;
+        last_bits[42] = b'V';
+        assert_eq!(decode_32(&last_bits), Err(DecodeError::NonCanonical));
+    }
+
+    #[test]
+    fn test_encode_all_short_inputs() {
+        for a in 0..=255u8 {
+            assert_eq!(encode(&[a]), URL_SAFE_NO_PAD.encode([a]).as_bytes());
+            for b in 0..=255u8 {
+                assert_eq!(encode(&[a, b]), URL_SAFE_NO_PAD.encode([a, b]).as_bytes());
+            }
+        }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_decode_all_short_inputs() {
+        // Every byte value in a 2-character string, and every 3-character string over the
+        // alphabet, padding and a few characters from other alphabets
+        for a in 0..=255u8 {
+            for b in 0..=255u8 {
+                assert_matches_reference(&[a, b]);
+            }
+        }
+        let characters: Vec<u8> = ALPHABET.iter().chain(b"=+/.").copied().collect();
+        for &a in &characters {
+            for &b in &characters {
+                for &c in &characters {
+                    assert_matches_reference(&[a, b, c]);
+                }
+            }
+        }
+    }
+
+    proptest! {
+        #[test]
+        fn test_round_trip(input in proptest::collection::vec(any::<u8>(), 0..256)) {
+            let encoded = encode(&input);
+            prop_assert_eq!(&encoded, &URL_SAFE_NO_PAD.encode(&input).into_bytes());
+            prop_assert_eq!(decode(&encoded), Ok(input));
+        }
+
+        #[test]
+        fn test_decode_matches_reference(input in "[A-Za-z0-9_=+/-]{0,64}") {
+            assert_matches_reference(input.as_bytes());
+        }
+
+        #[test]
+        fn test_decode_arbitrary_bytes(input in proptest::collection::vec(any::<u8>(), 0..64)) {
+            assert_matches_reference(&input);
+        }
+    }
+}
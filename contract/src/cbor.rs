@@ -0,0 +1,212 @@
+//! Bounded, allocation-free CBOR (RFC 8949) decoder for WebAuthn structures
+//!
+//! Authenticators encode COSE keys and attestation objects with CTAP2's canonical
+//! CBOR, so only definite-length items are supported; indefinite lengths are
+//! rejected as malformed. Byte and text strings are borrowed from the input.
+
+/// Major types
+pub const MAJOR_UNSIGNED: u8 = 0;
+pub const MAJOR_NEGATIVE: u8 = 1;
+pub const MAJOR_BYTES: u8 = 2;
+pub const MAJOR_TEXT: u8 = 3;
+pub const MAJOR_ARRAY: u8 = 4;
+pub const MAJOR_MAP: u8 = 5;
+pub const MAJOR_TAG: u8 = 6;
+
+/// The input is not CBOR this decoder accepts, or an item has an unexpected type
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Malformed;
+
+/// Reads CBOR items one after another from a byte slice
+pub struct Decoder<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Decoder { input, pos: 0 }
+    }
+
+    /// Whether every item has been read
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.input.len()
+    }
+
+    /// Offset of the next item in the input
+    #[cfg(any(test, feature = "attestation"))]
+    pub fn position(&self) -> usize {
+        self.pos
+    }
+
+    /// Reads a signed integer (major type 0 or 1)
+    pub fn int(&mut self) -> Result<i64, Malformed> {
+        let (major, value) = self.header()?;
+        let value = i64::try_from(value).map_err(|_| Malformed)?;
+        match major {
+            MAJOR_UNSIGNED => Ok(value),
+            MAJOR_NEGATIVE => Ok(-1 - value),
+            _ => Err(Malformed),
+        }
+    }
+
+    /// Reads a byte string
+    pub fn bytes(&mut self) -> Result<&'a [u8], Malformed> {
+        self.string(MAJOR_BYTES)
+    }
+
+    /// Reads a text string, without checking it is UTF-8
+    #[cfg(any(test, feature = "attestation"))]
+    pub fn text(&mut self) -> Result<&'a [u8], Malformed> {
+        self.string(MAJOR_TEXT)
+    }
+
+    /// Reads an array header and returns its number of items
+    #[cfg(any(test, feature = "attestation"))]
+    pub fn array(&mut self) -> Result<u64, Malformed> {
+        self.expect(MAJOR_ARRAY)
+    }
+
+    /// Reads a map header and returns its number of entries
+    pub fn map(&mut self) -> Result<u64, Malformed> {
+        self.expect(MAJOR_MAP)
+    }
+
+    /// Skips the next item, including everything nested in it
+    pub fn skip(&mut self) -> Result<(), Malformed> {
+        let mut pending: u64 = 1;
+        while pending > 0 {
+            pending -= 1;
+            let (major, value) = self.header()?;
+            let nested = match major {
+                MAJOR_BYTES | MAJOR_TEXT => {
+                    self.take(value)?;
+                    0
+                }
+                MAJOR_ARRAY => value,
+                MAJOR_MAP => value.checked_mul(2).ok_or(Malformed)?,
+                MAJOR_TAG => 1,
+                _ => 0,
+            };
+            // Every nested item takes at least one byte, which bounds the loop
+            if nested > (self.input.len() - self.pos) as u64 {
+                return Err(Malformed);
+            }
+            pending += nested;
+        }
+        Ok(())
+    }
+
+    fn string(&mut self, expected: u8) -> Result<&'a [u8], Malformed> {
+        let len = self.expect(expected)?;
+        self.take(len)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<u64, Malformed> {
+        match self.header()? {
+            (major, value) if major == expected => Ok(value),
+            _ => Err(Malformed),
+        }
+    }
+
+    /// Reads an initial byte and its argument
+    fn header(&mut self) -> Result<(u8, u64), Malformed> {
+        let initial = self.take(1)?[0];
+        let major = initial >> 5;
+        let value = match initial & 0x1f {
+            info @ 0..=23 => u64::from(info),
+            24 => u64::from(self.take(1)?[0]),
+            25 => u64::from(u16::from_be_bytes(self.take_array()?)),
+            26 => u64::from(u32::from_be_bytes(self.take_array()?)),
+            27 => u64::from_be_bytes(self.take_array()?),
+            // Reserved values and indefinite lengths
+            _ => return Err(Malformed),
+        };
+        Ok((major, value))
+    }
+
+    fn take(&mut self, len: u64) -> Result<&'a [u8], Malformed> {
+        let remaining = self.input.len() - self.pos;
+        let len = usize::try_from(len).map_err(|_| Malformed)?;
+        if len > remaining {
+            return Err(Malformed);
+        }
+        let taken = &self.input[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(taken)
+    }
+
+    fn take_array<const N: usize>(&mut self) -> Result<[u8; N], Malformed> {
+        let mut array = [0u8; N];
+        array.copy_from_slice(self.take(N as u64)?);
+        Ok(array)
+    }
+}
+
+#[cfg(test)]
+mod cbor_test {
+    use super::*;
+
+    #[test]
+    fn test_integers() {
+        // 0, 23, 24, 1000, -1, -7, -1000
+        let input = [
+            0x00, 0x17, 0x18, 0x18, 0x19, 0x03, 0xe8, 0x20, 0x26, 0x39, 0x03, 0xe7,
+        ];
+        let mut decoder = Decoder::new(&input);
+        for expected in [0, 23, 24, 1000, -1, -7, -1000] {
+            assert_eq!(decoder.int(), Ok(expected));
+        }
+        assert!(decoder.is_empty());
+    }
+
+    #[test]
+    fn test_strings_and_containers() {
+        // {"fmt": "none", 1: h'0102', 2: [1, [2]]}
+        let input = [
+            0xa3, 0x63, b'f', b'm', b't', 0x64, b'n', b'o', b'n', b'e', 0x01, 0x42, 0x01, 0x02,
+            0x02, 0x82, 0x01, 0x81, 0x02,
+        ];
+        let mut decoder = Decoder::new(&input);
+        assert_eq!(decoder.map(), Ok(3));
+        assert_eq!(decoder.text(), Ok(&b"fmt"[..]));
+        assert_eq!(decoder.text(), Ok(&b"none"[..]));
+        assert_eq!(decoder.int(), Ok(1));
+        assert_eq!(decoder.bytes(), Ok(&[0x01, 0x02][..]));
+        assert_eq!(decoder.int(), Ok(2));
+        let nested = decoder.position();
+        assert_eq!(decoder.skip(), Ok(()));
+        assert!(decoder.is_empty());
+        assert_eq!(decoder.skip(), Err(Malformed));
+
+        let mut decoder = Decoder::new(&input[nested..]);
+        assert_eq!(decoder.array(), Ok(2));
+        assert_eq!(decoder.int(), Ok(1));
+        assert_eq!(decoder.array(), Ok(1));
+    }
+
+    #[test]
+    fn test_rejects_malformed() {
+        // Truncated string, indefinite length, wrong type, reserved additional info
+        assert_eq!(Decoder::new(&[0x43, 0x01]).bytes(), Err(Malformed));
+        assert_eq!(
+            Decoder::new(&[0x5f, 0x41, 0x01, 0xff]).bytes(),
+            Err(Malformed)
+        );
+        assert_eq!(Decoder::new(&[0x41, 0x01]).text(), Err(Malformed));
+        assert_eq!(Decoder::new(&[0x1c]).int(), Err(Malformed));
+        assert_eq!(Decoder::new(&[]).int(), Err(Malformed));
+
+        // Integers that do not fit an i64
+        let input = [0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(Decoder::new(&input).int(), Err(Malformed));
+
+        // Containers claiming more items than the input could hold
+        let input = [0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(Decoder::new(&input).skip(), Err(Malformed));
+        assert_eq!(
+            Decoder::new(&[0xa2, 0x01, 0x02, 0x03]).skip(),
+            Err(Malformed)
+        );
+    }
+}
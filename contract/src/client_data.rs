@@ -0,0 +1,564 @@
+//! Bounded, allocation-free parser for WebAuthn clientDataJSON
+//!
+//! Only the members the account contracts care about are extracted (`type`,
+//! `challenge`, `origin`, `crossOrigin` and `topOrigin`). Any other member is
+//! validated and skipped. String values are kept as borrowed, still-escaped
+//! slices of the input and are unescaped lazily through [`JsonStr::bytes`].
+
+use soroban_sdk::{Bytes, Env, Vec};
+
+/// Maximum accepted size of clientDataJSON
+pub const MAX_CLIENT_DATA_LEN: usize = 1024;
+
+/// Maximum accepted length of an unescaped origin
+const MAX_ORIGIN_LEN: usize = 256;
+
+/// Maximum number of members in the top-level object
+const MAX_MEMBERS: usize = 16;
+
+/// Maximum nesting depth of skipped values
+const MAX_DEPTH: u32 = 8;
+
+/// Reasons clientDataJSON can be rejected
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ClientDataError {
+    /// The input is larger than `MAX_CLIENT_DATA_LEN`
+    TooLarge,
+    /// The input is not well-formed UTF-8 JSON
+    Malformed,
+    /// The top-level object has more than `MAX_MEMBERS` members
+    TooManyMembers,
+    /// A key appears more than once in the top-level object
+    DuplicateKey,
+    /// A known member holds a value of the wrong JSON type
+    InvalidFieldType,
+    /// `type`, `challenge` or `origin` is missing
+    MissingField,
+}
+
+/// A JSON string value, borrowed from the input without its quotes and still escaped
+#[derive(Copy, Clone, Debug)]
+pub struct JsonStr<'a> {
+    raw: &'a [u8],
+}
+
+/// The members of clientDataJSON used for assertion verification
+#[derive(Copy, Clone, Debug)]
+pub struct ClientData<'a> {
+    pub type_: JsonStr<'a>,
+    pub challenge: JsonStr<'a>,
+    pub origin: JsonStr<'a>,
+    pub cross_origin: Option<bool>,
+    pub top_origin: Option<JsonStr<'a>>,
+}
+
+/// Parses clientDataJSON
+pub fn parse(json: &[u8]) -> Result<ClientData<'_>, ClientDataError> {
+    if json.len() > MAX_CLIENT_DATA_LEN {
+        return Err(ClientDataError::TooLarge);
+    }
+    core::str::from_utf8(json).map_err(|_| ClientDataError::Malformed)?;
+
+    let mut parser = Parser {
+        input: json,
+        pos: 0,
+    };
+    let mut keys = [JsonStr { raw: &[] }; MAX_MEMBERS];
+    let mut key_count = 0;
+
+    let mut type_ = None;
+    let mut challenge = None;
+    let mut origin = None;
+    let mut cross_origin = None;
+    let mut top_origin = None;
+
+    parser.skip_whitespace();
+    parser.expect(b'{')?;
+    parser.skip_whitespace();
+
+    if parser.peek() == Some(b'}') {
+        parser.pos += 1;
+    } else {
+        loop {
+            parser.skip_whitespace();
+            let key = parser.string()?;
+
+            if keys[..key_count].iter().any(|seen| seen.eq_json(&key)) {
+                return Err(ClientDataError::DuplicateKey);
+            }
+            if key_count == MAX_MEMBERS {
+                return Err(ClientDataError::TooManyMembers);
+            }
+            keys[key_count] = key;
+            key_count += 1;
+
+            parser.skip_whitespace();
+            parser.expect(b':')?;
+            parser.skip_whitespace();
+            let value = parser.value(0)?;
+
+            if key.eq_str(b"type") {
+                type_ = Some(value.string()?);
+            } else if key.eq_str(b"challenge") {
+                challenge = Some(value.string()?);
+            } else if key.eq_str(b"origin") {
+                origin = Some(value.string()?);
+            } else if key.eq_str(b"crossOrigin") {
+                cross_origin = Some(value.boolean()?);
+            } else if key.eq_str(b"topOrigin") {
+                top_origin = Some(value.string()?);
+            }
+
+            parser.skip_whitespace();
+            match parser.next() {
+                Some(b',') => continue,
+                Some(b'}') => break,
+                _ => return Err(ClientDataError::Malformed),
+            }
+        }
+    }
+
+    parser.skip_whitespace();
+    if parser.pos != json.len() {
+        return Err(ClientDataError::Malformed);
+    }
+
+    Ok(ClientData {
+        type_: type_.ok_or(ClientDataError::MissingField)?,
+        challenge: challenge.ok_or(ClientDataError::MissingField)?,
+        origin: origin.ok_or(ClientDataError::MissingField)?,
+        cross_origin,
+        top_origin,
+    })
+}
+
+impl ClientData<'_> {
+    /// Whether the assertion was produced by `navigator.credentials.get`
+    pub fn is_get(&self) -> bool {
+        self.type_.eq_str(b"webauthn.get")
+    }
+
+    /// Whether the credential was created by `navigator.credentials.create`
+    pub fn is_create(&self) -> bool {
+        self.type_.eq_str(b"webauthn.create")
+    }
+
+    /// Whether `origin` is one of `allowed_origins` and, if the ceremony ran in a
+    /// cross-origin iframe, so is the `topOrigin` of the page embedding it
+    pub fn origin_allowed(&self, env: &Env, allowed_origins: &Vec<Bytes>) -> bool {
+        if !is_allowed(env, &self.origin, allowed_origins) {
+            return false;
+        }
+        match (self.cross_origin, self.top_origin) {
+            (_, Some(top_origin)) => is_allowed(env, &top_origin, allowed_origins),
+            // A cross-origin ceremony without `topOrigin` cannot show who embedded it
+            (Some(true), None) => false,
+            _ => true,
+        }
+    }
+}
+
+/// Whether an origin is one of `allowed_origins`
+fn is_allowed(env: &Env, origin: &JsonStr, allowed_origins: &Vec<Bytes>) -> bool {
+    let mut buffer = [0u8; MAX_ORIGIN_LEN];
+    match origin.unescape_into(&mut buffer) {
+        Some(origin) => allowed_origins.contains(Bytes::from_slice(env, origin)),
+        None => false,
+    }
+}
+
+impl<'a> JsonStr<'a> {
+    /// Iterates over the unescaped UTF-8 bytes of the string
+    pub fn bytes(&self) -> Unescaped<'a> {
+        Unescaped {
+            raw: self.raw,
+            pos: 0,
+            pending: [0; 4],
+            pending_pos: 0,
+            pending_len: 0,
+        }
+    }
+
+    /// Compares the unescaped string with `expected`
+    pub fn eq_str(&self, expected: &[u8]) -> bool {
+        self.bytes().eq(expected.iter().copied())
+    }
+
+    /// Compares two strings by their unescaped value
+    pub fn eq_json(&self, other: &JsonStr) -> bool {
+        self.bytes().eq(other.bytes())
+    }
+
+    /// Writes the unescaped string into `buffer`, or returns `None` if it does not fit
+    pub fn unescape_into<'b>(&self, buffer: &'b mut [u8]) -> Option<&'b [u8]> {
+        let mut len = 0;
+        for byte in self.bytes() {
+            *buffer.get_mut(len)? = byte;
+            len += 1;
+        }
+        Some(&buffer[..len])
+    }
+}
+
+/// Iterator over the unescaped bytes of a [`JsonStr`]
+///
+/// The string has already been validated by the parser, so escapes are well formed.
+pub struct Unescaped<'a> {
+    raw: &'a [u8],
+    pos: usize,
+    pending: [u8; 4],
+    pending_pos: u8,
+    pending_len: u8,
+}
+
+impl Iterator for Unescaped<'_> {
+    type Item = u8;
+
+    fn next(&mut self) -> Option<u8> {
+        if self.pending_pos < self.pending_len {
+            let byte = self.pending[self.pending_pos as usize];
+            self.pending_pos += 1;
+            return Some(byte);
+        }
+
+        let byte = *self.raw.get(self.pos)?;
+        self.pos += 1;
+        if byte != b'\\' {
+            return Some(byte);
+        }
+
+        let escape = self.raw[self.pos];
+        self.pos += 1;
+        let unescaped = match escape {
+            b'b' => 0x08,
+            b'f' => 0x0c,
+            b'n' => b'\n',
+            b'r' => b'\r',
+            b't' => b'\t',
+            b'u' => {
+                let mut code_point = hex4(&self.raw[self.pos..])?;
+                self.pos += 4;
+                if is_high_surrogate(code_point) {
+                    // Skip the `\u` of the low surrogate
+                    let low = hex4(&self.raw[self.pos + 2..])?;
+                    self.pos += 6;
+                    code_point = 0x10000 + ((code_point - 0xd800) << 10) + (low - 0xdc00);
+                }
+
+                let encoded = char::from_u32(code_point)?.encode_utf8(&mut self.pending);
+                self.pending_len = encoded.len() as u8;
+                self.pending_pos = 1;
+                self.pending[0]
+            }
+            other => other,
+        };
+        Some(unescaped)
+    }
+}
+
+/// A parsed JSON value, keeping only what the known members can hold
+enum Value<'a> {
+    String(JsonStr<'a>),
+    Bool(bool),
+    Other,
+}
+
+impl<'a> Value<'a> {
+    fn string(self) -> Result<JsonStr<'a>, ClientDataError> {
+        match self {
+            Value::String(value) => Ok(value),
+            _ => Err(ClientDataError::InvalidFieldType),
+        }
+    }
+
+    fn boolean(self) -> Result<bool, ClientDataError> {
+        match self {
+            Value::Bool(value) => Ok(value),
+            _ => Err(ClientDataError::InvalidFieldType),
+        }
+    }
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek()?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    fn expect(&mut self, expected: u8) -> Result<(), ClientDataError> {
+        match self.next() {
+            Some(byte) if byte == expected => Ok(()),
+            _ => Err(ClientDataError::Malformed),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn value(&mut self, depth: u32) -> Result<Value<'a>, ClientDataError> {
+        if depth > MAX_DEPTH {
+            return Err(ClientDataError::Malformed);
+        }
+
+        match self.peek() {
+            Some(b'"') => Ok(Value::String(self.string()?)),
+            Some(b't') => self.literal(b"true").map(|_| Value::Bool(true)),
+            Some(b'f') => self.literal(b"false").map(|_| Value::Bool(false)),
+            Some(b'n') => self.literal(b"null").map(|_| Value::Other),
+            Some(b'{') => self.container(b'}', depth).map(|_| Value::Other),
+            Some(b'[') => self.container(b']', depth).map(|_| Value::Other),
+            Some(b'-' | b'0'..=b'9') => self.number().map(|_| Value::Other),
+            _ => Err(ClientDataError::Malformed),
+        }
+    }
+
+    fn literal(&mut self, literal: &[u8]) -> Result<(), ClientDataError> {
+        if !self.input[self.pos..].starts_with(literal) {
+            return Err(ClientDataError::Malformed);
+        }
+        self.pos += literal.len();
+        Ok(())
+    }
+
+    /// Skips a nested object or array; keys of nested objects are not checked for duplicates
+    fn container(&mut self, close: u8, depth: u32) -> Result<(), ClientDataError> {
+        self.pos += 1;
+        self.skip_whitespace();
+        if self.peek() == Some(close) {
+            self.pos += 1;
+            return Ok(());
+        }
+
+        loop {
+            self.skip_whitespace();
+            if close == b'}' {
+                self.string()?;
+                self.skip_whitespace();
+                self.expect(b':')?;
+                self.skip_whitespace();
+            }
+            self.value(depth + 1)?;
+            self.skip_whitespace();
+            match self.next() {
+                Some(b',') => continue,
+                Some(byte) if byte == close => return Ok(()),
+                _ => return Err(ClientDataError::Malformed),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<(), ClientDataError> {
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+        }
+        match self.next() {
+            Some(b'0') => {}
+            Some(b'1'..=b'9') => self.digits(),
+            _ => return Err(ClientDataError::Malformed),
+        }
+        if self.peek() == Some(b'.') {
+            self.pos += 1;
+            self.digits1()?;
+        }
+        if matches!(self.peek(), Some(b'e' | b'E')) {
+            self.pos += 1;
+            if matches!(self.peek(), Some(b'+' | b'-')) {
+                self.pos += 1;
+            }
+            self.digits1()?;
+        }
+        Ok(())
+    }
+
+    fn digits(&mut self) {
+        while matches!(self.peek(), Some(b'0'..=b'9')) {
+            self.pos += 1;
+        }
+    }
+
+    fn digits1(&mut self) -> Result<(), ClientDataError> {
+        if !matches!(self.peek(), Some(b'0'..=b'9')) {
+            return Err(ClientDataError::Malformed);
+        }
+        self.digits();
+        Ok(())
+    }
+
+    /// Validates a string and returns its raw contents
+    fn string(&mut self) -> Result<JsonStr<'a>, ClientDataError> {
+        self.expect(b'"')?;
+        let start = self.pos;
+
+        loop {
+            match self.next().ok_or(ClientDataError::Malformed)? {
+                b'"' => break,
+                b'\\' => match self.next().ok_or(ClientDataError::Malformed)? {
+                    b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't' => {}
+                    b'u' => self.unicode_escape()?,
+                    _ => return Err(ClientDataError::Malformed),
+                },
+                0x00..=0x1f => return Err(ClientDataError::Malformed),
+                _ => {}
+            }
+        }
+
+        Ok(JsonStr {
+            raw: &self.input[start..self.pos - 1],
+        })
+    }
+
+    /// Validates the `XXXX` of a `\uXXXX` escape, including surrogate pairs
+    fn unicode_escape(&mut self) -> Result<(), ClientDataError> {
+        let code_point = self.hex4()?;
+        if is_high_surrogate(code_point) {
+            self.literal(b"\\u")?;
+            let low = self.hex4()?;
+            if !(0xdc00..0xe000).contains(&low) {
+                return Err(ClientDataError::Malformed);
+            }
+        } else if (0xdc00..0xe000).contains(&code_point) {
+            return Err(ClientDataError::Malformed);
+        }
+        Ok(())
+    }
+
+    fn hex4(&mut self) -> Result<u32, ClientDataError> {
+        let value = hex4(&self.input[self.pos..]).ok_or(ClientDataError::Malformed)?;
+        self.pos += 4;
+        Ok(value)
+    }
+}
+
+fn is_high_surrogate(code_point: u32) -> bool {
+    (0xd800..0xdc00).contains(&code_point)
+}
+
+/// Reads four hex digits
+fn hex4(input: &[u8]) -> Option<u32> {
+    input.get(..4)?.iter().try_fold(0u32, |value, digit| {
+        let digit = (*digit as char).to_digit(16)?;
+        Some((value << 4) | digit)
+    })
+}
+
+#[cfg(test)]
+mod client_data_test {
+    use super::*;
+
+    const CLIENT_DATA: &[u8] = br#"{"type":"webauthn.get","challenge":"dGVzdA","origin":"https://localhost","crossOrigin":false}"#;
+
+    #[test]
+    fn test_parse_browser_client_data() {
+        let client_data = parse(CLIENT_DATA).unwrap();
+        assert!(client_data.type_.eq_str(b"webauthn.get"));
+        assert!(client_data.challenge.eq_str(b"dGVzdA"));
+        assert!(client_data.origin.eq_str(b"https://localhost"));
+        assert_eq!(client_data.cross_origin, Some(false));
+        assert!(client_data.top_origin.is_none());
+    }
+
+    #[test]
+    fn test_parse_extra_members_and_whitespace() {
+        let json = br#" {
+            "type" : "webauthn.get",
+            "challenge" : "dGVzdA",
+            "origin" : "https://app.example",
+            "crossOrigin" : true,
+            "topOrigin" : "https://top.example",
+            "other_keys_can_be_added_here" : "do not compare clientDataJSON against a template",
+            "extension" : { "nested" : [1, -2.5e3, null, true, {"a": "b"}] }
+        } "#;
+
+        let client_data = parse(json).unwrap();
+        assert!(client_data.origin.eq_str(b"https://app.example"));
+        assert_eq!(client_data.cross_origin, Some(true));
+        assert!(client_data
+            .top_origin
+            .unwrap()
+            .eq_str(b"https://top.example"));
+    }
+
+    #[test]
+    fn test_parse_escapes() {
+        let json = br#"{"type":"webauthn\u002eget","challenge":"\u0064GVzdA","origin":"https:\/\/caf\u00e9.example\ud83d\ude00"}"#;
+        let client_data = parse(json).unwrap();
+        assert!(client_data.type_.eq_str(b"webauthn.get"));
+        assert!(client_data.challenge.eq_str(b"dGVzdA"));
+        assert!(client_data
+            .origin
+            .eq_str("https://caf\u{e9}.example\u{1f600}".as_bytes()));
+
+        let mut buffer = [0u8; 8];
+        assert_eq!(
+            client_data.challenge.unescape_into(&mut buffer),
+            Some(&b"dGVzdA"[..])
+        );
+        assert_eq!(client_data.origin.unescape_into(&mut buffer), None);
+    }
+
+    #[test]
+    fn test_parse_rejects_duplicate_keys() {
+        let json =
+            br#"{"type":"webauthn.get","challenge":"dGVzdA","origin":"a","challenge":"b3RoZXI"}"#;
+        assert_eq!(parse(json).unwrap_err(), ClientDataError::DuplicateKey);
+
+        // Keys are compared by their unescaped value
+        let json = br#"{"type":"webauthn.get","challenge":"dGVzdA","origin":"a","type":"x"}"#;
+        assert_eq!(parse(json).unwrap_err(), ClientDataError::DuplicateKey);
+    }
+
+    #[test]
+    fn test_parse_rejects_malformed_input() {
+        let cases: [&[u8]; 11] = [
+            b"",
+            b"[]",
+            br#"{"type":"webauthn.get""#,
+            br#"{"type":"webauthn.get",}"#,
+            br#"{"type":"webauthn.get"} trailing"#,
+            br#"{"type":"bad \x escape"}"#,
+            br#"{"type":"lone \udc00 surrogate"}"#,
+            br#"{"type":"unpaired \ud800 surrogate"}"#,
+            b"{\"type\":\"raw \n newline\"}",
+            br#"{"n":01}"#,
+            br#"{"n":[[[[[[[[[[1]]]]]]]]]]}"#,
+        ];
+        for json in cases {
+            assert_eq!(parse(json).unwrap_err(), ClientDataError::Malformed);
+        }
+        assert_eq!(
+            parse(b"{\"type\":\"\xff\"}").unwrap_err(),
+            ClientDataError::Malformed
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_missing_and_mistyped_fields() {
+        let json = br#"{"type":"webauthn.get","origin":"a"}"#;
+        assert_eq!(parse(json).unwrap_err(), ClientDataError::MissingField);
+
+        let json = br#"{"type":"webauthn.get","challenge":1,"origin":"a"}"#;
+        assert_eq!(parse(json).unwrap_err(), ClientDataError::InvalidFieldType);
+
+        let json = br#"{"type":"webauthn.get","challenge":"a","origin":"a","crossOrigin":"false"}"#;
+        assert_eq!(parse(json).unwrap_err(), ClientDataError::InvalidFieldType);
+    }
+
+    #[test]
+    fn test_parse_rejects_oversized_input() {
+        let json = [b' '; MAX_CLIENT_DATA_LEN + 1];
+        assert_eq!(parse(&json).unwrap_err(), ClientDataError::TooLarge);
+    }
+}
@@ -0,0 +1,189 @@
+//! Bounded, allocation-free DER reader for attestation signatures and certificates
+//!
+//! Only what `packed` attestation needs is supported: definite lengths of up to two
+//! bytes, single-byte tags, and reading a TLV's contents as a borrowed slice.
+
+/// Universal tags
+pub const TAG_BOOLEAN: u8 = 0x01;
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_SEQUENCE: u8 = 0x30;
+
+/// The input is not DER this reader accepts, or a value has an unexpected tag
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct Malformed;
+
+/// Reads DER values one after another from a byte slice
+pub struct Reader<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(input: &'a [u8]) -> Self {
+        Reader { input, pos: 0 }
+    }
+
+    /// Whether every value has been read
+    pub fn is_empty(&self) -> bool {
+        self.pos == self.input.len()
+    }
+
+    /// The input not read yet
+    pub fn remaining(&self) -> &'a [u8] {
+        &self.input[self.pos..]
+    }
+
+    /// Tag of the next value, if any
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.input.get(self.pos).copied()
+    }
+
+    /// Reads a value with the given tag and returns its contents
+    pub fn read(&mut self, tag: u8) -> Result<&'a [u8], Malformed> {
+        let (actual, contents) = self.read_any()?;
+        if actual != tag {
+            return Err(Malformed);
+        }
+        Ok(contents)
+    }
+
+    /// Reads a value with the given tag if it is the next one
+    pub fn read_optional(&mut self, tag: u8) -> Result<Option<&'a [u8]>, Malformed> {
+        match self.peek_tag() {
+            Some(actual) if actual == tag => self.read(tag).map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// Reads the next value and returns its tag and contents
+    pub fn read_any(&mut self) -> Result<(u8, &'a [u8]), Malformed> {
+        let tag = self.take(1)?[0];
+        // High tag numbers are not used by the structures read here
+        if tag & 0x1f == 0x1f {
+            return Err(Malformed);
+        }
+
+        let len = match self.take(1)?[0] {
+            len @ 0..=0x7f => usize::from(len),
+            0x81 => match self.take(1)?[0] {
+                len @ 0x80..=0xff => usize::from(len),
+                _ => return Err(Malformed),
+            },
+            0x82 => match u16::from_be_bytes([self.take(1)?[0], self.take(1)?[0]]) {
+                len @ 0x100.. => usize::from(len),
+                _ => return Err(Malformed),
+            },
+            _ => return Err(Malformed),
+        };
+        Ok((tag, self.take(len)?))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Malformed> {
+        if len > self.input.len() - self.pos {
+            return Err(Malformed);
+        }
+        let taken = &self.input[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(taken)
+    }
+}
+
+/// Converts a DER `ECDSA-Sig-Value` into the raw 64-byte `r || s` form
+///
+/// The `s` component is not normalized.
+pub fn ecdsa_signature(der: &[u8]) -> Result<[u8; 64], Malformed> {
+    let mut outer = Reader::new(der);
+    let mut sequence = Reader::new(outer.read(TAG_SEQUENCE)?);
+    if !outer.is_empty() {
+        return Err(Malformed);
+    }
+
+    let mut signature = [0u8; 64];
+    for scalar in signature.chunks_mut(32) {
+        let mut integer = sequence.read(TAG_INTEGER)?;
+        // A leading zero keeps a positive integer's top bit clear
+        if integer.len() == 33 && integer[0] == 0 {
+            integer = &integer[1..];
+        }
+        if integer.is_empty() || integer.len() > 32 {
+            return Err(Malformed);
+        }
+        scalar[32 - integer.len()..].copy_from_slice(integer);
+    }
+
+    if !sequence.is_empty() {
+        return Err(Malformed);
+    }
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod der_test {
+    use super::*;
+
+    #[test]
+    fn test_read_values() {
+        // SEQUENCE { INTEGER 5, OCTET STRING 'ab' }, BOOLEAN true
+        let input = [
+            0x30, 0x07, 0x02, 0x01, 0x05, 0x04, 0x02, 0xab, 0xcd, 0x01, 0x01, 0xff,
+        ];
+        let mut reader = Reader::new(&input);
+        let mut sequence = Reader::new(reader.read(TAG_SEQUENCE).unwrap());
+        assert_eq!(sequence.read(TAG_INTEGER), Ok(&[0x05][..]));
+        assert_eq!(sequence.read_optional(TAG_BOOLEAN), Ok(None));
+        assert_eq!(
+            sequence.read_optional(TAG_OCTET_STRING),
+            Ok(Some(&[0xab, 0xcd][..]))
+        );
+        assert!(sequence.is_empty());
+        assert_eq!(reader.read_any(), Ok((TAG_BOOLEAN, &[0xff][..])));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_long_lengths() {
+        let mut input = [0u8; 3 + 200];
+        input[..3].copy_from_slice(&[0x04, 0x81, 200]);
+        assert_eq!(
+            Reader::new(&input).read(TAG_OCTET_STRING).unwrap().len(),
+            200
+        );
+
+        // Lengths must use the shortest form
+        assert_eq!(
+            Reader::new(&[0x04, 0x81, 0x01, 0x00]).read_any(),
+            Err(Malformed)
+        );
+        assert_eq!(
+            Reader::new(&[0x04, 0x82, 0x00, 0x01, 0x00]).read_any(),
+            Err(Malformed)
+        );
+        // Indefinite and truncated lengths
+        assert_eq!(
+            Reader::new(&[0x30, 0x80, 0x00, 0x00]).read_any(),
+            Err(Malformed)
+        );
+        assert_eq!(Reader::new(&[0x04, 0x03, 0x00]).read_any(), Err(Malformed));
+    }
+
+    #[test]
+    fn test_ecdsa_signature() {
+        // r with a leading zero, s shorter than 32 bytes
+        let mut der = [0u8; 2 + 35 + 4];
+        der[..4].copy_from_slice(&[0x30, 39, 0x02, 33]);
+        der[4] = 0x00;
+        der[5..37].copy_from_slice(&[0x80; 32]);
+        der[37..].copy_from_slice(&[0x02, 0x02, 0x12, 0x34]);
+
+        let signature = ecdsa_signature(&der).unwrap();
+        assert_eq!(signature[..32], [0x80; 32]);
+        assert_eq!(signature[32..62], [0u8; 30]);
+        assert_eq!(signature[62..], [0x12, 0x34]);
+
+        assert_eq!(ecdsa_signature(&der[..40]), Err(Malformed));
+        assert_eq!(ecdsa_signature(&[0x30, 0x00]), Err(Malformed));
+    }
+}
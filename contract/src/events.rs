@@ -0,0 +1,241 @@
+//! Events published by the account, forming its security log
+//!
+//! Topics are stable: the first topic names the event and the second, when present,
+//! identifies the signer (credential ID), guardian, session key, token or policy
+//! contract it concerns. Events published by a call that then fails are rolled back.
+
+use soroban_sdk::{Address, Bytes, BytesN, Env, IntoVal, Map, Symbol, Val, Vec};
+
+use crate::{
+    AttestationConfig, BackupPolicy, Policy, RecoveryConfig, SessionPublicKey, SpendLimit,
+    TtlConfig,
+};
+
+fn publish<D: IntoVal<Env, Val>>(env: &Env, name: &str, subject: Option<Val>, data: D) {
+    let mut topics: Vec<Val> = Vec::new(env);
+    topics.push_back(Symbol::new(env, name).into_val(env));
+    if let Some(subject) = subject {
+        topics.push_back(subject);
+    }
+    env.events().publish(topics, data);
+}
+
+/// `["initialized", credential_id]`, data `public_key`
+pub fn initialized(env: &Env, credential_id: &Bytes, public_key: &BytesN<64>) {
+    let subject = Some(credential_id.into_val(env));
+    publish(env, "initialized", subject, public_key.clone());
+}
+
+/// `["signer_added", credential_id]`, data `(public_key, weight)`
+pub fn signer_added(env: &Env, credential_id: &Bytes, public_key: &BytesN<64>, weight: u32) {
+    let subject = Some(credential_id.into_val(env));
+    publish(env, "signer_added", subject, (public_key.clone(), weight));
+}
+
+/// `["signer_removed", credential_id]`, no data
+pub fn signer_removed(env: &Env, credential_id: &Bytes) {
+    publish(env, "signer_removed", Some(credential_id.into_val(env)), ());
+}
+
+/// `["signer_attested", credential_id]`, data `aaguid` of the authenticator
+pub fn signer_attested(env: &Env, credential_id: &Bytes, aaguid: &BytesN<16>) {
+    publish(
+        env,
+        "signer_attested",
+        Some(credential_id.into_val(env)),
+        aaguid.clone(),
+    );
+}
+
+/// `["owner_updated", credential_id]`, data `public_key` of the passkey that replaced
+/// every signer through `update_owner`
+pub fn owner_updated(env: &Env, credential_id: &Bytes, public_key: &BytesN<64>) {
+    let subject = Some(credential_id.into_val(env));
+    publish(env, "owner_updated", subject, public_key.clone());
+}
+
+/// `["clone_detected", credential_id]`, data `(stored_sign_count, sign_count)`
+///
+/// Published by `report_clone`, since `__check_auth` rolls back what it publishes when it
+/// rejects the counter.
+pub fn clone_detected(env: &Env, credential_id: &Bytes, stored_sign_count: u32, sign_count: u32) {
+    let subject = Some(credential_id.into_val(env));
+    publish(
+        env,
+        "clone_detected",
+        subject,
+        (stored_sign_count, sign_count),
+    );
+}
+
+/// `["threshold_changed"]`, data `threshold`
+pub fn threshold_changed(env: &Env, threshold: u32) {
+    publish(env, "threshold_changed", None, threshold);
+}
+
+/// `["signer_policy_set", credential_id]`, data `policy`
+pub fn signer_policy_set(env: &Env, credential_id: &Bytes, policy: &Policy) {
+    let subject = Some(credential_id.into_val(env));
+    publish(env, "signer_policy_set", subject, policy.clone());
+}
+
+/// `["signer_policy_removed", credential_id]`, no data
+pub fn signer_policy_removed(env: &Env, credential_id: &Bytes) {
+    publish(
+        env,
+        "signer_policy_removed",
+        Some(credential_id.into_val(env)),
+        (),
+    );
+}
+
+/// `["backup_policy_set", credential_id]`, data `policy`
+pub fn backup_policy_set(env: &Env, credential_id: &Bytes, policy: BackupPolicy) {
+    publish(
+        env,
+        "backup_policy_set",
+        Some(credential_id.into_val(env)),
+        policy,
+    );
+}
+
+/// `["origin_added"]`, data `origin`
+pub fn origin_added(env: &Env, origin: &Bytes) {
+    publish(env, "origin_added", None, origin.clone());
+}
+
+/// `["origin_removed"]`, data `origin`
+pub fn origin_removed(env: &Env, origin: &Bytes) {
+    publish(env, "origin_removed", None, origin.clone());
+}
+
+/// `["user_verification_set"]`, data `required`
+pub fn user_verification_set(env: &Env, required: bool) {
+    publish(env, "user_verification_set", None, required);
+}
+
+/// `["attestation_config_set"]`, data `config`
+pub fn attestation_config_set(env: &Env, config: &AttestationConfig) {
+    publish(env, "attestation_config_set", None, config.clone());
+}
+
+/// `["attestation_config_removed"]`, no data
+pub fn attestation_config_removed(env: &Env) {
+    publish(env, "attestation_config_removed", None, ());
+}
+
+/// `["recovery_config_set"]`, data `config`
+pub fn recovery_config_set(env: &Env, config: &RecoveryConfig) {
+    publish(env, "recovery_config_set", None, config.clone());
+}
+
+/// `["recovery_started", guardian]`, data `(credential_id, execute_after)`
+pub fn recovery_started(env: &Env, guardian: &Address, credential_id: &Bytes, execute_after: u64) {
+    let subject = Some(guardian.into_val(env));
+    publish(
+        env,
+        "recovery_started",
+        subject,
+        (credential_id.clone(), execute_after),
+    );
+}
+
+/// `["recovery_approved", guardian]`, data `approvals`
+pub fn recovery_approved(env: &Env, guardian: &Address, approvals: u32) {
+    publish(
+        env,
+        "recovery_approved",
+        Some(guardian.into_val(env)),
+        approvals,
+    );
+}
+
+/// `["recovery_cancelled"]`, data `credential_id` of the replacement passkey
+pub fn recovery_cancelled(env: &Env, credential_id: &Bytes) {
+    publish(env, "recovery_cancelled", None, credential_id.clone());
+}
+
+/// `["recovery_completed", credential_id]`, data `public_key` of the passkey that
+/// replaced every signer
+pub fn recovery_completed(env: &Env, credential_id: &Bytes, public_key: &BytesN<64>) {
+    let subject = Some(credential_id.into_val(env));
+    publish(env, "recovery_completed", subject, public_key.clone());
+}
+
+/// `["session_key_added", public_key]`, data `(expiration_ledger, spend_limits)`
+pub fn session_key_added(
+    env: &Env,
+    public_key: &SessionPublicKey,
+    expiration_ledger: u32,
+    spend_limits: &Map<Address, i128>,
+) {
+    let subject = Some(public_key.into_val(env));
+    let data = (expiration_ledger, spend_limits.clone());
+    publish(env, "session_key_added", subject, data);
+}
+
+/// `["session_key_revoked", public_key]`, no data
+pub fn session_key_revoked(env: &Env, public_key: &SessionPublicKey) {
+    publish(
+        env,
+        "session_key_revoked",
+        Some(public_key.into_val(env)),
+        (),
+    );
+}
+
+/// `["spend_limit_set", token]`, data `spend_limit`
+pub fn spend_limit_set(env: &Env, token: &Address, spend_limit: &SpendLimit) {
+    let subject = Some(token.into_val(env));
+    publish(env, "spend_limit_set", subject, spend_limit.clone());
+}
+
+/// `["spend_limit_removed", token]`, no data
+pub fn spend_limit_removed(env: &Env, token: &Address) {
+    publish(env, "spend_limit_removed", Some(token.into_val(env)), ());
+}
+
+/// `["policy_installed", policy]`, no data
+pub fn policy_installed(env: &Env, policy: &Address) {
+    publish(env, "policy_installed", Some(policy.into_val(env)), ());
+}
+
+/// `["policy_uninstalled", policy]`, no data
+pub fn policy_uninstalled(env: &Env, policy: &Address) {
+    publish(env, "policy_uninstalled", Some(policy.into_val(env)), ());
+}
+
+/// `["upgrade_delay_set"]`, data `delay`
+pub fn upgrade_delay_set(env: &Env, delay: u64) {
+    publish(env, "upgrade_delay_set", None, delay);
+}
+
+/// `["upgrade_scheduled"]`, data `(wasm_hash, execute_after)`
+pub fn upgrade_scheduled(env: &Env, wasm_hash: &BytesN<32>, execute_after: u64) {
+    publish(
+        env,
+        "upgrade_scheduled",
+        None,
+        (wasm_hash.clone(), execute_after),
+    );
+}
+
+/// `["upgrade_cancelled"]`, data `wasm_hash`
+pub fn upgrade_cancelled(env: &Env, wasm_hash: &BytesN<32>) {
+    publish(env, "upgrade_cancelled", None, wasm_hash.clone());
+}
+
+/// `["upgraded"]`, data `wasm_hash`
+pub fn upgraded(env: &Env, wasm_hash: &BytesN<32>) {
+    publish(env, "upgraded", None, wasm_hash.clone());
+}
+
+/// `["migrated"]`, data `(from_version, to_version)`
+pub fn migrated(env: &Env, from_version: u32, to_version: u32) {
+    publish(env, "migrated", None, (from_version, to_version));
+}
+
+/// `["ttl_config_set"]`, data `config`
+pub fn ttl_config_set(env: &Env, config: &TtlConfig) {
+    publish(env, "ttl_config_set", None, config.clone());
+}
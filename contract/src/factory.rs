@@ -1,18 +1,16 @@
-#![no_std]
 use soroban_sdk::{
-    contract, contracterror, contractimpl, symbol_short, vec, Address, BytesN, Env, Symbol,
+    contract, contractimpl, symbol_short, vec, xdr::ToXdr, Address, Bytes, BytesN, Env, IntoVal,
+    Symbol, Vec,
+};
+
+use crate::{
+    authenticator_data, check_authenticator_data, is_valid_signature_encoding, public_key,
+    verify_client_data, verify_signature, Error, Signature,
 };
 
 #[contract]
 pub struct Factory;
 
-#[contracterror]
-#[derive(Copy, Clone, Eq, PartialEq, Debug)]
-pub enum Error {
-    NotInited = 1,
-    AlreadyInited = 2,
-}
-
 const STORAGE_KEY_WASM_HASH: Symbol = symbol_short!("hash");
 
 #[contractimpl]
@@ -31,10 +29,10 @@ impl Factory {
             .extend_ttl_for_contract_instance(contract_address.clone(), max_ttl, max_ttl);
     }
 
-    /// Inicializa el factory con el hash del contrato WebAuthn
+    /// Inicializa el factory con el hash del contrato de la cuenta passkey
     pub fn init(env: Env, wasm_hash: BytesN<32>) -> Result<(), Error> {
         if env.storage().instance().has(&STORAGE_KEY_WASM_HASH) {
-            return Err(Error::AlreadyInited);
+            return Err(Error::AlreadyInitialized);
         }
 
         env.storage()
@@ -46,19 +44,486 @@ impl Factory {
         Ok(())
     }
 
-    /// Despliega una nueva cuenta WebAuthn
-    pub fn deploy(env: Env, salt: BytesN<32>, pk: BytesN<65>) -> Result<Address, Error> {
-        let wasm_hash = env
-            .storage()
-            .instance()
-            .get::<Symbol, BytesN<32>>(&STORAGE_KEY_WASM_HASH)
-            .ok_or(Error::NotInited)?;
+    /// Despliega una nueva cuenta passkey con su primera passkey, sus orígenes
+    /// permitidos, su RP ID y la política de verificación del usuario
+    ///
+    /// La clave pública puede ser un punto SEC1, comprimido (33 bytes) o sin comprimir
+    /// (65 bytes), las coordenadas `X || Y` (64 bytes) o una COSE_Key. El factory
+    /// comprueba que es un punto de la curva P-256 y la pasa a `init` como `X || Y`,
+    /// ya que la cuenta no valida la curva.
+    ///
+    /// Cambio incompatible: el salt de despliegue ahora es `sha256("salt" || salt)`, no
+    /// `salt`, así que un mismo `salt` lleva a otra dirección que con versiones
+    /// anteriores del factory. Las cuentas ya desplegadas no cambian de dirección.
+    ///
+    /// # Errors
+    /// * `InvalidPublicKey` - Si la clave pública no es un punto P-256 válido
+    pub fn deploy(
+        env: Env,
+        salt: BytesN<32>,
+        credential_id: Bytes,
+        pk: Bytes,
+        origins: Vec<Bytes>,
+        rp_id: Bytes,
+        require_uv: bool,
+    ) -> Result<Address, Error> {
+        let coordinates = public_key::decode(&pk).map_err(|_| Error::InvalidPublicKey)?;
+        let salt = user_salt(&env, &salt);
+        deploy_with_salt(
+            &env,
+            salt,
+            credential_id,
+            &coordinates,
+            origins,
+            rp_id,
+            require_uv,
+        )
+    }
 
-        let address = env.deployer().with_current_contract(salt).deploy(wasm_hash);
-        let () = env.invoke_contract(&address, &symbol_short!("init"), vec![&env, pk.to_val()]);
+    /// Dirección en la que `deploy` despliega la cuenta con este salt, exista o no
+    pub fn get_address(env: Env, salt: BytesN<32>) -> Address {
+        env.deployer()
+            .with_current_contract(user_salt(&env, &salt))
+            .deployed_address()
+    }
 
-        Self::extend_ttl(env);
+    /// Dirección en la que `deploy_for_key` despliega la cuenta de esta clave pública
+    pub fn get_key_address(env: Env, pk: Bytes) -> Result<Address, Error> {
+        let coordinates = public_key::decode(&pk).map_err(|_| Error::InvalidPublicKey)?;
+        Ok(env
+            .deployer()
+            .with_current_contract(key_salt(&env, &coordinates))
+            .deployed_address())
+    }
+
+    /// Despliega la cuenta de una passkey en la única dirección que le corresponde
+    ///
+    /// El salt es `sha256(X || Y)` de la clave pública, así que todas sus codificaciones
+    /// llevan a la misma dirección. Para que nadie pueda adelantarse y ocupar esa
+    /// dirección con otros orígenes, RP ID o política, `assertion` debe ser una
+    /// aserción `webauthn.get` de la propia passkey cuyo challenge es
+    /// `sha256("deploy_for_key" || XDR de la dirección del factory || XDR de
+    /// (credential_id, origins, rp_id, require_uv))`, desde uno de `origins` y para
+    /// `rp_id`.
+    ///
+    /// # Errors
+    /// * `InvalidPublicKey` - Si la clave pública no es un punto P-256 válido
+    /// * `InvalidSignature` - Si la firma de la aserción está mal formada
+    /// * Los errores de las comprobaciones de la aserción, como en `__check_auth`
+    pub fn deploy_for_key(
+        env: Env,
+        credential_id: Bytes,
+        pk: Bytes,
+        origins: Vec<Bytes>,
+        rp_id: Bytes,
+        require_uv: bool,
+        assertion: Signature,
+    ) -> Result<Address, Error> {
+        let coordinates = public_key::decode(&pk).map_err(|_| Error::InvalidPublicKey)?;
+        let parameters = (
+            credential_id.clone(),
+            origins.clone(),
+            rp_id.clone(),
+            require_uv,
+        );
+        let mut preimage = Bytes::from_slice(&env, b"deploy_for_key");
+        preimage.append(&env.current_contract_address().to_xdr(&env));
+        preimage.append(&parameters.to_xdr(&env));
+        let challenge = env.crypto().sha256(&preimage).to_bytes();
+
+        // La misma comprobación que hace la cuenta con las aserciones de sus passkeys
+        if !is_valid_signature_encoding(&assertion.signature.to_array()) {
+            return Err(Error::InvalidSignature);
+        }
+        verify_client_data(&env, &assertion.client_data_json, &challenge, &origins)?;
+        let data = authenticator_data::parse(&assertion.authenticator_data)
+            .map_err(|_| Error::InvalidAuthenticatorData)?;
+        let rp_id_hash = env.crypto().sha256(&rp_id).to_bytes();
+        check_authenticator_data(&data, &rp_id_hash, require_uv)?;
+        verify_signature(&env, &BytesN::from_array(&env, &coordinates), &assertion);
+
+        let salt = key_salt(&env, &coordinates);
+        deploy_with_salt(
+            &env,
+            salt,
+            credential_id,
+            &coordinates,
+            origins,
+            rp_id,
+            require_uv,
+        )
+    }
+}
+
+/// Despliega e inicializa una cuenta con el salt de despliegue ya derivado y las
+/// coordenadas `X || Y` de su primera passkey
+fn deploy_with_salt(
+    env: &Env,
+    salt: BytesN<32>,
+    credential_id: Bytes,
+    coordinates: &[u8; 64],
+    origins: Vec<Bytes>,
+    rp_id: Bytes,
+    require_uv: bool,
+) -> Result<Address, Error> {
+    let wasm_hash = env
+        .storage()
+        .instance()
+        .get::<Symbol, BytesN<32>>(&STORAGE_KEY_WASM_HASH)
+        .ok_or(Error::NotInitialized)?;
+
+    let address = env.deployer().with_current_contract(salt).deploy(wasm_hash);
+    let () = env.invoke_contract(
+        &address,
+        &symbol_short!("init"),
+        vec![
+            env,
+            Bytes::from_array(env, coordinates).to_val(),
+            credential_id.to_val(),
+            origins.to_val(),
+            rp_id.to_val(),
+            require_uv.into_val(env),
+        ],
+    );
+
+    Factory::extend_ttl(env.clone());
+
+    Ok(address)
+}
+
+/// Salt de despliegue de `deploy`: `sha256("salt" || salt)`
+fn user_salt(env: &Env, salt: &BytesN<32>) -> BytesN<32> {
+    let mut preimage = Bytes::from_slice(env, b"salt");
+    preimage.append(&salt.into());
+    env.crypto().sha256(&preimage).to_bytes()
+}
+
+/// Salt de despliegue de `deploy_for_key`: `sha256(X || Y)` de la clave pública
+fn key_salt(env: &Env, coordinates: &[u8; 64]) -> BytesN<32> {
+    env.crypto()
+        .sha256(&Bytes::from_array(env, coordinates))
+        .to_bytes()
+}
+
+#[cfg(test)]
+mod factory_test {
+    use super::*;
+    use crate::base64_url;
+    use p256::ecdsa::{signature::hazmat::PrehashSigner, SigningKey};
+    use soroban_sdk::testutils::BytesN as _;
+
+    #[test]
+    fn test_init() {
+        let env = Env::default();
+        let contract_id = env.register_contract(None, Factory);
+        let client = FactoryClient::new(&env, &contract_id);
+        let wasm_hash = BytesN::random(&env);
+
+        client.init(&wasm_hash);
+        assert_eq!(
+            client.try_init(&wasm_hash),
+            Err(Ok(Error::AlreadyInitialized))
+        );
+    }
+
+    #[test]
+    fn test_deploy_requires_init() {
+        let env = Env::default();
+        let contract_id = env.register_contract(None, Factory);
+        let client = FactoryClient::new(&env, &contract_id);
+
+        let pk = signing_key(7).verifying_key().to_encoded_point(false);
+        let result = client.try_deploy(
+            &BytesN::random(&env),
+            &Bytes::from_slice(&env, b"credential"),
+            &Bytes::from_slice(&env, pk.as_bytes()),
+            &Vec::new(&env),
+            &Bytes::from_slice(&env, b"localhost"),
+            &true,
+        );
+        assert_eq!(result, Err(Ok(Error::NotInitialized)));
+    }
+
+    /// Módulo wasm mínimo cuyo `init` acepta los cinco argumentos y no hace nada, para
+    /// desplegar cuentas sin compilar la real a wasm
+    const STUB_ACCOUNT_WASM: &[u8] = &[
+        0x00, 0x61, 0x73, 0x6d, 0x01, 0x00, 0x00, 0x00, // cabecera
+        0x01, 0x0a, 0x01, 0x60, 0x05, 0x7e, 0x7e, 0x7e, 0x7e, 0x7e, 0x01, 0x7e, // tipos
+        0x03, 0x02, 0x01, 0x00, // funciones
+        0x07, 0x08, 0x01, 0x04, b'i', b'n', b'i', b't', 0x00, 0x00, // exports
+        0x0a, 0x06, 0x01, 0x04, 0x00, 0x42, 0x02, 0x0b, // código: devuelve Void
+        0x00, 0x1e, 0x11, b'c', b'o', b'n', b't', b'r', b'a', b'c', b't', b'e', b'n', b'v', b'm',
+        b'e', b't', b'a', b'v', b'0', // contractenvmetav0: protocolo 21
+        0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x15, 0x00, 0x00, 0x00, 0x00,
+    ];
 
-        Ok(address)
+    fn signing_key(seed: u8) -> SigningKey {
+        SigningKey::from_bytes(&[seed; 32].into()).unwrap()
     }
-}
\ No newline at end of file
+
+    fn origins(env: &Env) -> Vec<Bytes> {
+        vec![env, Bytes::from_slice(env, b"https://localhost")]
+    }
+
+    /// Aserción `webauthn.get` de `key` sobre `challenge`, con UP y UV
+    fn sign_assertion(
+        env: &Env,
+        key: &SigningKey,
+        challenge: &BytesN<32>,
+        rp_id: &[u8],
+    ) -> Signature {
+        let mut client_data_json =
+            Bytes::from_slice(env, br#"{"type":"webauthn.get","challenge":""#);
+        client_data_json.extend_from_array(&base64_url::encode_32(&challenge.to_array()));
+        client_data_json.extend_from_slice(br#"","origin":"https://localhost"}"#);
+
+        let mut authenticator_data = Bytes::from_array(
+            env,
+            &env.crypto()
+                .sha256(&Bytes::from_slice(env, rp_id))
+                .to_array(),
+        );
+        authenticator_data.extend_from_array(&[0x05, 0, 0, 0, 0]);
+
+        let mut message = authenticator_data.clone();
+        message.extend_from_array(&env.crypto().sha256(&client_data_json).to_array());
+        let digest = env.crypto().sha256(&message).to_array();
+        let signature: p256::ecdsa::Signature = key.sign_prehash(&digest).unwrap();
+        let signature = signature.normalize_s().unwrap_or(signature);
+
+        Signature {
+            credential_id: Bytes::from_slice(env, b"credential"),
+            authenticator_data,
+            client_data_json,
+            signature: BytesN::from_array(env, &signature.to_bytes().into()),
+        }
+    }
+
+    /// Challenge que la passkey firma para `deploy_for_key` con estos parámetros
+    fn deploy_challenge(
+        env: &Env,
+        factory: &Address,
+        credential_id: &Bytes,
+        origins: &Vec<Bytes>,
+        rp_id: &Bytes,
+        require_uv: bool,
+    ) -> BytesN<32> {
+        let parameters = (
+            credential_id.clone(),
+            origins.clone(),
+            rp_id.clone(),
+            require_uv,
+        );
+        let mut preimage = Bytes::from_slice(env, b"deploy_for_key");
+        preimage.append(&factory.clone().to_xdr(env));
+        preimage.append(&parameters.to_xdr(env));
+        env.crypto().sha256(&preimage).to_bytes()
+    }
+
+    #[test]
+    fn test_get_address() {
+        let env = Env::default();
+        let contract_id = env.register_contract(None, Factory);
+        let client = FactoryClient::new(&env, &contract_id);
+        let salt = BytesN::random(&env);
+
+        // La dirección depende solo del factory y del salt
+        assert_eq!(client.get_address(&salt), client.get_address(&salt));
+        assert_ne!(
+            client.get_address(&salt),
+            client.get_address(&BytesN::random(&env))
+        );
+
+        // La de una clave depende solo de ella, en cualquier codificación
+        let key = signing_key(7);
+        let point = key.verifying_key().to_encoded_point(false);
+        let uncompressed = Bytes::from_slice(&env, point.as_bytes());
+        let compressed =
+            Bytes::from_slice(&env, key.verifying_key().to_encoded_point(true).as_bytes());
+        let address = client.get_key_address(&uncompressed);
+        assert_eq!(client.get_key_address(&compressed), address);
+        assert_eq!(client.get_key_address(&uncompressed.slice(1..)), address);
+        let other = signing_key(8).verifying_key().to_encoded_point(false);
+        assert_ne!(
+            client.get_key_address(&Bytes::from_slice(&env, other.as_bytes())),
+            address
+        );
+
+        // y ningún salt de `deploy` lleva a ella
+        let coordinates = Bytes::from_slice(&env, &point.as_bytes()[1..]);
+        let key_salt = env.crypto().sha256(&coordinates).to_bytes();
+        assert_ne!(client.get_address(&key_salt), address);
+
+        assert_eq!(
+            client.try_get_key_address(&Bytes::from_array(&env, &[4u8; 65])),
+            Err(Ok(Error::InvalidPublicKey))
+        );
+    }
+
+    #[test]
+    fn test_deploy_at_predicted_address() {
+        let env = Env::default();
+        let contract_id = env.register_contract(None, Factory);
+        let client = FactoryClient::new(&env, &contract_id);
+        client.init(&env.deployer().upload_contract_wasm(STUB_ACCOUNT_WASM));
+
+        let key = signing_key(7);
+        let pk = Bytes::from_slice(&env, key.verifying_key().to_encoded_point(false).as_bytes());
+        let credential_id = Bytes::from_slice(&env, b"credential");
+        let rp_id = Bytes::from_slice(&env, b"localhost");
+
+        let salt = BytesN::random(&env);
+        let predicted = client.get_address(&salt);
+        assert_eq!(
+            client.deploy(&salt, &credential_id, &pk, &origins(&env), &rp_id, &true),
+            predicted
+        );
+
+        // La cuenta no comprueba la curva, así que el factory rechaza los puntos fuera
+        let mut off_curve = pk.clone();
+        off_curve.set(64, pk.get(64).unwrap() ^ 1);
+        let salt = BytesN::random(&env);
+        let result = client.try_deploy(
+            &salt,
+            &credential_id,
+            &off_curve,
+            &origins(&env),
+            &rp_id,
+            &true,
+        );
+        assert_eq!(result, Err(Ok(Error::InvalidPublicKey)));
+
+        let challenge = deploy_challenge(
+            &env,
+            &contract_id,
+            &credential_id,
+            &origins(&env),
+            &rp_id,
+            true,
+        );
+        let assertion = sign_assertion(&env, &key, &challenge, b"localhost");
+        let predicted = client.get_key_address(&pk);
+        assert_eq!(
+            client.deploy_for_key(
+                &credential_id,
+                &pk,
+                &origins(&env),
+                &rp_id,
+                &true,
+                &assertion
+            ),
+            predicted
+        );
+
+        // Una cuenta ya desplegada no se puede volver a desplegar
+        assert!(client
+            .try_deploy_for_key(
+                &credential_id,
+                &pk,
+                &origins(&env),
+                &rp_id,
+                &true,
+                &assertion
+            )
+            .is_err());
+    }
+
+    #[test]
+    fn test_deploy_for_key_requires_assertion() {
+        let env = Env::default();
+        let contract_id = env.register_contract(None, Factory);
+        let client = FactoryClient::new(&env, &contract_id);
+        client.init(&env.deployer().upload_contract_wasm(STUB_ACCOUNT_WASM));
+
+        let key = signing_key(7);
+        let pk = Bytes::from_slice(&env, key.verifying_key().to_encoded_point(false).as_bytes());
+        let credential_id = Bytes::from_slice(&env, b"credential");
+        let rp_id = Bytes::from_slice(&env, b"localhost");
+        let challenge = deploy_challenge(
+            &env,
+            &contract_id,
+            &credential_id,
+            &origins(&env),
+            &rp_id,
+            true,
+        );
+        let assertion = sign_assertion(&env, &key, &challenge, b"localhost");
+
+        // Quien se adelante con la aserción no puede cambiar los parámetros que cubre
+        let other_origins = vec![&env, Bytes::from_slice(&env, b"https://evil.example")];
+        let other_rp_id = Bytes::from_slice(&env, b"evil.example");
+        let other_credential_id = Bytes::from_slice(&env, b"other");
+        for (credential_id, origins, rp_id, require_uv, error) in [
+            (
+                &other_credential_id,
+                origins(&env),
+                &rp_id,
+                true,
+                Error::ChallengeMismatch,
+            ),
+            (
+                &credential_id,
+                origins(&env),
+                &other_rp_id,
+                true,
+                Error::ChallengeMismatch,
+            ),
+            (
+                &credential_id,
+                origins(&env),
+                &rp_id,
+                false,
+                Error::ChallengeMismatch,
+            ),
+            (
+                &credential_id,
+                other_origins,
+                &rp_id,
+                true,
+                Error::OriginNotAllowed,
+            ),
+        ] {
+            let result = client.try_deploy_for_key(
+                credential_id,
+                &pk,
+                &origins,
+                rp_id,
+                &require_uv,
+                &assertion,
+            );
+            assert_eq!(result, Err(Ok(error)));
+        }
+
+        // ni usar una aserción para otro relying party
+        let elsewhere = sign_assertion(&env, &key, &challenge, b"evil.example");
+        assert_eq!(
+            client.try_deploy_for_key(
+                &credential_id,
+                &pk,
+                &origins(&env),
+                &rp_id,
+                &true,
+                &elsewhere
+            ),
+            Err(Ok(Error::RpIdHashMismatch))
+        );
+
+        // y otra passkey no puede ocupar la dirección de esta
+        let forged = sign_assertion(&env, &signing_key(8), &challenge, b"localhost");
+        assert!(client
+            .try_deploy_for_key(&credential_id, &pk, &origins(&env), &rp_id, &true, &forged)
+            .is_err());
+
+        assert_eq!(
+            client.deploy_for_key(
+                &credential_id,
+                &pk,
+                &origins(&env),
+                &rp_id,
+                &true,
+                &assertion
+            ),
+            client.get_key_address(&pk)
+        );
+    }
+}
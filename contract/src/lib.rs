@@ -1,21 +1,66 @@
 //! Passkey Account Contract for Soroban
-//! 
+//!
 //! This contract implements a custom account that uses secp256r1 (P-256) signatures
 //! for authentication, enabling WebAuthn/Passkey integration.
-//! 
+//!
 //! Features:
 //! - WebAuthn compatible (secp256r1/ES256 signatures)
+//! - Passkeys registered from raw or uncompressed SEC1 public keys, or through the
+//!   factory from compressed SEC1 or COSE keys too
+//! - Optional `packed` attestation on registration, with an AAGUID allow-list and
+//!   trusted certificate issuers, verified by a separate contract
+//! - Per-signer device-bound or synced (BE/BS flags) passkey requirements
+//! - Signature counter checks, with possible passkey clones recorded by `report_clone`
 //! - Biometric authentication (Face ID, Touch ID, Windows Hello)
 //! - No passwords required
+//! - Multiple passkeys with an M-of-N weighted threshold
+//! - Guardian-based social recovery with a timelock
+//! - Time-limited, scoped session keys (ed25519 or secp256r1)
+//! - Per-signer policies on the contracts, functions and amounts a passkey may authorize
+//! - Per-token spending limits over rolling 24-hour windows
+//! - Pluggable external policy contracts
+//! - Self-upgrade with a versioned storage layout and an optional timelock
+//! - Automatic TTL extension so the account is not archived while in use
+//! - Off-chain message signatures checked with `is_valid_signature`
 //! - Implements Soroban's CustomAccountInterface
+//!
+//! Accounts deployed from the original single-passkey contracts have no upgrade entry
+//! point, so they cannot be converted in place. To move one to this contract, deploy an
+//! account for the same passkey with the factory's `deploy_for_key`, then have the old
+//! account transfer its assets there; its passkey still authorizes those transfers.
+//! Trustlines and allowances are not carried over and must be set up again.
 
 #![no_std]
 
+#[cfg(any(test, feature = "attestation"))]
+mod attestation;
+mod attestation_contract;
+mod authenticator_data;
+pub mod base64_url;
+#[cfg(any(test, feature = "factory", feature = "attestation"))]
+mod cbor;
+mod client_data;
+#[cfg(any(test, feature = "attestation"))]
+mod der;
+mod events;
+mod policy;
+mod policy_contract;
+mod public_key;
+mod session;
+
+pub use attestation_contract::{
+    AttestationContract, AttestationContractClient, VerifiedAttestation,
+};
+pub use policy::{AllowedCall, Policy};
+pub use policy_contract::{Authorizer, PolicyContract, PolicyContractClient};
+pub use session::{SessionKey, SessionPublicKey, SessionSignature};
+
 use soroban_sdk::{
-    auth::{Context, CustomAccountInterface},
-    contract, contracterror, contractimpl, contracttype,
+    auth::{Context, ContractContext, CustomAccountInterface},
+    contract, contracterror, contracttype,
     crypto::Hash,
-    Bytes, BytesN, Env, Vec,
+    xdr::ToXdr,
+    Address, Bytes, BytesN, Env, IntoVal, Map, Symbol, Vec,
 };
 
 /// The main contract struct
@@ -23,162 +68,2314 @@ use soroban_sdk::{
 pub struct PasskeyAccount;
 
 /// Storage keys for the contract
-#[contracttype]
+#[contracttype(export = false)]
 #[derive(Clone)]
 pub enum DataKey {
-    /// The owner's secp256r1 public key (64 bytes uncompressed: 32 bytes X + 32 bytes Y)
-    Owner,
-    /// Optional: Store credential ID for easier retrieval
-    CredentialId,
+    /// Credential IDs of all registered passkeys
+    Signers,
+    /// A registered passkey, keyed by its WebAuthn credential ID
+    Signer(Bytes),
+    /// Origins (e.g. `https://app.example`) assertions may come from
+    AllowedOrigins,
+    /// SHA-256 of the WebAuthn relying party ID (e.g. `app.example`)
+    RpIdHash,
+    /// Whether assertions must carry the User Verified (biometric/PIN) flag
+    RequireUserVerification,
+    /// Total signer weight required to authorize an operation
+    Threshold,
+    /// Guardians allowed to replace the passkeys
+    RecoveryConfig,
+    /// Key replacement started by the guardians, if any
+    Recovery,
+    /// Public keys of all registered session keys
+    SessionKeys,
+    /// A registered session key, keyed by its public key
+    SessionKey(SessionPublicKey),
+    /// Rules restricting what a passkey may authorize, keyed by its credential ID
+    SignerPolicy(Bytes),
+    /// Whether a passkey must be device-bound or synced, keyed by its credential ID
+    BackupPolicy(Bytes),
+    /// Spending limit of a token, keyed by the token contract
+    SpendLimit(Address),
+    /// Amounts of a token transferred or approved in the last 24 hours, by the hour
+    /// (persistent storage, so spends do not grow the instance)
+    Spending(Address),
+    /// External policy contracts consulted by `__check_auth`
+    PolicyContracts,
+    /// Version of the storage layout
+    Version,
+    /// Seconds an upgrade must wait before it can be applied
+    UpgradeDelay,
+    /// Upgrade waiting for its delay to pass, if any
+    PendingUpgrade,
+    /// When and how far the instance TTL is extended
+    TtlConfig,
+    /// Authenticators new passkeys must be attested by, if attestation is required
+    AttestationConfig,
+    /// Evidence that a passkey was cloned, keyed by its credential ID
+    CloneReport(Bytes),
+}
+
+/// A passkey allowed to sign for the account
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Signer {
+    /// secp256r1 public key (64 bytes uncompressed: 32 bytes X + 32 bytes Y)
+    pub public_key: BytesN<64>,
+    /// Last signature counter reported by the authenticator
+    pub sign_count: u32,
+    /// Weight this passkey contributes towards the threshold
+    pub weight: u32,
+}
+
+/// A signature counter that did not increase, reported through `report_clone`
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct CloneReport {
+    /// Counter the account had recorded for the passkey
+    pub stored_sign_count: u32,
+    /// Counter the authenticator reported
+    pub sign_count: u32,
+    /// Ledger sequence the report was made in
+    pub ledger: u32,
+}
+
+/// Guardians that can recover the account if every passkey is lost
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RecoveryConfig {
+    /// Addresses allowed to start and approve a recovery
+    pub guardians: Vec<Address>,
+    /// Number of guardian approvals needed to execute a recovery
+    pub quorum: u32,
+    /// Seconds between starting a recovery and being able to execute it
+    pub delay: u64,
+}
+
+/// A pending key replacement
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Recovery {
+    /// WebAuthn credential ID of the replacement passkey
+    pub credential_id: Bytes,
+    /// secp256r1 public key of the replacement passkey
+    pub public_key: BytesN<64>,
+    /// Guardians that approved the replacement
+    pub approvals: Vec<Address>,
+    /// Ledger timestamp from which the replacement can be executed
+    pub execute_after: u64,
+}
+
+/// Limit on how much of a token the account may move in any 24-hour window
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpendLimit {
+    /// Maximum total amount transferred or approved within 24 hours
+    pub limit: i128,
+    /// Total passkey weight that may exceed the limit (e.g. an admin passkey or a quorum)
+    pub override_weight: u32,
+}
+
+/// Total amount of a token transferred or approved within one hour
+#[contracttype(export = false)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SpendBucket {
+    /// Hours since the Unix epoch (ledger timestamp / 3600)
+    pub hour: u64,
+    pub amount: i128,
+}
+
+/// An upgrade waiting for the upgrade delay to pass
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PendingUpgrade {
+    /// Hash of the uploaded WASM to switch to
+    pub wasm_hash: BytesN<32>,
+    /// Ledger timestamp from which the upgrade can be applied
+    pub execute_after: u64,
+}
+
+/// When and how far the account's TTL is extended
+///
+/// The account's data lives in instance storage, which shares the instance's TTL, so
+/// extending it keeps every signer, policy and limit alive. Spending records are kept
+/// apart and extended the same way whenever they are written.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct TtlConfig {
+    /// Extend once the TTL drops below this many ledgers
+    pub threshold: u32,
+    /// Number of ledgers to extend the TTL to
+    pub extend_to: u32,
+}
+
+/// Authenticators new passkeys must be attested by
+///
+/// Attestation proves which authenticator model created a passkey, e.g. that its key
+/// lives in a hardware security key rather than a synced password manager.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AttestationConfig {
+    /// Attestation verifier contract (see `AttestationContract`) that checks signatures
+    pub verifier: Address,
+    /// AAGUIDs of the authenticator models allowed; empty allows any
+    pub aaguids: Vec<BytesN<16>>,
+    /// Public key (X || Y) trusted to sign the attestation certificates of each model,
+    /// e.g. its vendor's root from the FIDO Metadata Service; basic attestation from
+    /// other models is rejected
+    pub issuers: Map<BytesN<16>, BytesN<64>>,
+    /// Whether to accept self attestation, in which the passkey vouches for its own AAGUID
+    pub allow_self_attestation: bool,
+}
+
+/// Whether a passkey must be bound to one device or synced, e.g. through a cloud keychain
+///
+/// Checked against the Backup Eligibility (BE) and Backup State (BS) flags of the
+/// authenticator data at registration and on every assertion.
+#[contracttype]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum BackupPolicy {
+    /// Accept device-bound and synced passkeys
+    Any,
+    /// Only accept passkeys that can never leave their authenticator (BE unset)
+    DeviceBound,
+    /// Only accept passkeys that are backed up (BS set)
+    Synced,
 }
 
+/// Approximate number of ledgers closed per day (5 seconds per ledger)
+const DAY_IN_LEDGERS: u32 = 17_280;
+
+/// TTL configuration used until one is set: extend to 90 days when under 30 days
+const DEFAULT_TTL_CONFIG: TtlConfig = TtlConfig {
+    threshold: 30 * DAY_IN_LEDGERS,
+    extend_to: 90 * DAY_IN_LEDGERS,
+};
+
+/// Version of the storage layout this code expects
+pub const VERSION: u32 = 1;
+
+/// Shortest recovery delay, in seconds, so the signers always have a day to cancel
+const MIN_RECOVERY_DELAY: u64 = 24 * 60 * 60;
+
+/// Length of the rolling spending window, in hours
+///
+/// Spends are summed by the hour, and an hour's spends count until that hour plus
+/// the window has fully passed, i.e. for 24 to 25 hours.
+const SPEND_WINDOW_HOURS: u64 = 24;
+
 /// Error codes for the contract
 #[contracterror]
 #[derive(Copy, Clone, Debug, Eq, PartialEq, PartialOrd, Ord)]
 #[repr(u32)]
 pub enum Error {
-    /// Owner is already set
+    /// Signers are already set
     AlreadyInitialized = 1,
-    /// No owner set
+    /// No signer set
     NotInitialized = 2,
     /// Invalid public key format
     InvalidPublicKey = 3,
     /// Signature verification failed
     InvalidSignature = 4,
+    /// clientDataJSON is malformed or missing the challenge
+    InvalidClientData = 5,
+    /// clientDataJSON challenge does not match the signature payload
+    ChallengeMismatch = 6,
+    /// clientDataJSON is larger than the parser accepts
+    ClientDataTooLarge = 7,
+    /// clientDataJSON `type` is not `webauthn.get`
+    InvalidClientDataType = 8,
+    /// clientDataJSON `origin` is not on the account's allow-list
+    OriginNotAllowed = 9,
+    /// The origin allow-list would be left empty
+    EmptyOriginAllowList = 10,
+    /// Authenticator data is shorter than its 37-byte header
+    InvalidAuthenticatorData = 11,
+    /// Authenticator data was produced for a different relying party
+    RpIdHashMismatch = 12,
+    /// Authenticator data lacks the User Present flag
+    UserNotPresent = 13,
+    /// Authenticator data lacks the User Verified flag required by the account
+    UserNotVerified = 14,
+    /// The authenticator's signature counter did not increase (possible cloned authenticator)
+    SignCountNotIncreased = 15,
+    /// No signer is registered under the credential ID
+    SignerNotFound = 16,
+    /// A signer is already registered under the credential ID
+    SignerAlreadyExists = 17,
+    /// The last signer cannot be removed
+    LastSigner = 18,
+    /// Signer weights must be at least 1
+    InvalidWeight = 19,
+    /// The threshold must be at least 1 and reachable by the signers' total weight
+    InvalidThreshold = 20,
+    /// The same passkey signed more than once
+    DuplicateSignature = 21,
+    /// The signatures' total weight is below the threshold
+    ThresholdNotMet = 22,
+    /// Guardians contain duplicates, the quorum is 0 or above their count, or the
+    /// recovery delay is under 24 hours
+    InvalidGuardians = 23,
+    /// The address is not one of the account's guardians
+    NotGuardian = 24,
+    /// A recovery is already pending
+    RecoveryInProgress = 25,
+    /// No recovery is pending
+    NoRecoveryInProgress = 26,
+    /// The approval is for a different key than the pending recovery
+    RecoveryMismatch = 27,
+    /// The recovery lacks a quorum of approvals or its timelock has not expired
+    RecoveryNotReady = 28,
+    /// A session key is already expired, may call the account itself, or has a
+    /// negative spend limit
+    InvalidSessionKey = 29,
+    /// No session key is registered under the public key
+    SessionKeyNotFound = 30,
+    /// The session key's expiration ledger has passed
+    SessionKeyExpired = 31,
+    /// The session key may not authorize one of the calls
+    SessionKeyOutOfScope = 32,
+    /// The session key's spend limit would be exceeded
+    SpendLimitExceeded = 33,
+    /// Signers whose policies allow one of the calls do not reach the threshold
+    ContextNotAllowed = 34,
+    /// A spending limit is negative or its override weight is 0
+    InvalidSpendLimit = 35,
+    /// The token's 24-hour spending limit would be exceeded
+    DailyLimitExceeded = 36,
+    /// An external policy contract rejected the authorization or its installation
+    PolicyRejected = 37,
+    /// The policy contract is already installed
+    PolicyAlreadyInstalled = 38,
+    /// The policy contract is not installed
+    PolicyNotInstalled = 39,
+    /// An upgrade is already pending
+    UpgradeInProgress = 40,
+    /// No upgrade is pending
+    NoUpgradePending = 41,
+    /// The pending upgrade's delay has not passed
+    UpgradeNotReady = 42,
+    /// The upgrade delay can only be increased
+    InvalidUpgradeDelay = 43,
+    /// The stored data is from a newer version than this code
+    InvalidVersion = 44,
+    /// The TTL threshold is above the extension, or the extension above the maximum TTL
+    InvalidTtlConfig = 45,
+    /// The attestation object is too large or malformed, not `packed` ES256, or its
+    /// certificate is invalid
+    InvalidAttestation = 46,
+    /// The attestation is for an authenticator the account does not allow or trust
+    AttestationNotAllowed = 47,
+    /// New passkeys must be registered with an attestation
+    AttestationRequired = 48,
+    /// The passkey is synced while the signer must be device-bound, or the other way round
+    BackupStateNotAllowed = 49,
+    /// A signer is already registered with the public key, under another credential ID
+    PublicKeyAlreadyRegistered = 50,
+}
+
+impl From<client_data::ClientDataError> for Error {
+    fn from(error: client_data::ClientDataError) -> Self {
+        match error {
+            client_data::ClientDataError::TooLarge => Error::ClientDataTooLarge,
+            _ => Error::InvalidClientData,
+        }
+    }
+}
+
+/// A WebAuthn assertion as returned by `navigator.credentials.get`
+#[contracttype]
+#[derive(Clone)]
+pub struct Signature {
+    /// WebAuthn credential ID of the passkey that signed
+    pub credential_id: Bytes,
+    /// Raw authenticator data from the assertion response
+    pub authenticator_data: Bytes,
+    /// Raw clientDataJSON; its `challenge` is the base64url-encoded signature payload
+    pub client_data_json: Bytes,
+    /// secp256r1 signature (r || s, low-S normalized) over
+    /// `authenticatorData || sha256(clientDataJSON)`
+    pub signature: BytesN<64>,
+}
+
+/// What `__check_auth` accepts: assertions from the passkeys, or a session key signature
+#[contracttype]
+#[derive(Clone)]
+pub enum Authorization {
+    /// WebAuthn assertions whose signers' weights must reach the threshold
+    Passkeys(Vec<Signature>),
+    /// A session key signature, valid only within the key's scope
+    SessionKey(SessionSignature),
 }
 
-#[contractimpl]
+// Each contract feature builds its own wasm without the account's exports, which would
+// clash with the other contract's (`init`, `__check_auth`, ...)
+//
+// Entry points are described with plain comments: doc comments would be embedded in the
+// wasm's contract spec, which counts toward the network's 64 KiB size limit.
+#[cfg_attr(
+    not(any(feature = "factory", feature = "loan", feature = "attestation")),
+    soroban_sdk::contractimpl
+)]
 impl PasskeyAccount {
-    /// Initialize the contract with a secp256r1 public key from a passkey
-    /// 
-    /// # Arguments
-    /// * `public_key` - 64-byte uncompressed secp256r1 public key (X || Y coordinates)
-    /// * `credential_id` - Optional credential ID from WebAuthn (for reference)
-    /// 
-    /// # Errors
-    /// * `AlreadyInitialized` - If the contract is already initialized
-    /// * `InvalidPublicKey` - If the public key format is invalid
+    // Initialize the contract with a secp256r1 public key from a passkey
+    //
+    // The passkey is registered with weight 1 and the threshold starts at 1; more
+    // signers can be added and the threshold raised for M-of-N accounts.
+    //
+    // # Arguments
+    // * `public_key` - secp256r1 public key of the passkey (see `add_signer`)
+    // * `credential_id` - WebAuthn credential ID of the passkey
+    // * `allowed_origins` - Origins the passkey may be used from (at least one)
+    // * `rp_id` - WebAuthn relying party ID the passkey was registered for (e.g. `app.example`)
+    // * `require_user_verification` - Require biometric/PIN verification on every assertion
+    //
+    // # Errors
+    // * `AlreadyInitialized` - If the contract is already initialized
+    // * `InvalidPublicKey` - If the public key is not 64 or 65 uncompressed bytes
+    // * `EmptyOriginAllowList` - If no origin is given
     pub fn init(
         env: Env,
-        public_key: BytesN<64>,
-        credential_id: Option<Bytes>,
+        public_key: Bytes,
+        credential_id: Bytes,
+        allowed_origins: Vec<Bytes>,
+        rp_id: Bytes,
+        require_user_verification: bool,
     ) -> Result<(), Error> {
         // Check if already initialized
-        if env.storage().instance().has(&DataKey::Owner) {
+        if env.storage().instance().has(&DataKey::Signers) {
             return Err(Error::AlreadyInitialized);
         }
 
-        // Validate public key (basic check - should be 64 bytes)
-        if public_key.len() != 64 {
-            return Err(Error::InvalidPublicKey);
+        // Without an origin no assertion could ever be accepted
+        if allowed_origins.is_empty() {
+            return Err(Error::EmptyOriginAllowList);
+        }
+
+        let public_key = normalize_public_key(&env, &public_key)?;
+
+        // Register the passkey as the first signer
+        env.storage()
+            .instance()
+            .set(&DataKey::Signers, &Vec::<Bytes>::new(&env));
+        store_new_signer(&env, credential_id.clone(), public_key.clone(), 1)?;
+        env.storage().instance().set(&DataKey::Threshold, &1u32);
+
+        env.storage()
+            .instance()
+            .set(&DataKey::AllowedOrigins, &allowed_origins);
+        env.storage()
+            .instance()
+            .set(&DataKey::RpIdHash, &env.crypto().sha256(&rp_id).to_bytes());
+        env.storage().instance().set(
+            &DataKey::RequireUserVerification,
+            &require_user_verification,
+        );
+        env.storage().instance().set(&DataKey::Version, &VERSION);
+        extend_instance_ttl(&env);
+
+        events::initialized(&env, &credential_id, &public_key);
+
+        Ok(())
+    }
+
+    // Initialize the account with an attested passkey
+    //
+    // Like `init`, but the passkey's credential ID and public key are taken from the
+    // attestation object returned by `navigator.credentials.create`, whose `packed`
+    // attestation must verify and name an authenticator allowed by `attestation_config`.
+    // Passkeys added later must be attested too.
+    //
+    // # Arguments
+    // * `attestation_object` - CBOR attestation object of the new passkey
+    // * `client_data_json` - clientDataJSON of the registration (`webauthn.create`)
+    // * `attestation_config` - Authenticators passkeys must be attested by
+    // * `backup_policy` - Whether the passkey must be device-bound or synced
+    //
+    // # Errors
+    // * `AlreadyInitialized` - If the contract is already initialized
+    // * `EmptyOriginAllowList` - If no origin is given
+    // * `InvalidAttestation` - If the attestation is malformed or not `packed` ES256
+    // * `AttestationNotAllowed` - If the authenticator is not allowed
+    // * `BackupStateNotAllowed` - If the passkey does not satisfy `backup_policy`
+    // * Errors of clientDataJSON and authenticator data checks, as in `__check_auth`
+    #[allow(clippy::too_many_arguments)]
+    pub fn init_attested(
+        env: Env,
+        attestation_object: Bytes,
+        client_data_json: Bytes,
+        allowed_origins: Vec<Bytes>,
+        rp_id: Bytes,
+        require_user_verification: bool,
+        attestation_config: AttestationConfig,
+        backup_policy: BackupPolicy,
+    ) -> Result<(), Error> {
+        if env.storage().instance().has(&DataKey::Signers) {
+            return Err(Error::AlreadyInitialized);
+        }
+        if allowed_origins.is_empty() {
+            return Err(Error::EmptyOriginAllowList);
         }
 
-        // Store the public key
-        env.storage().instance().set(&DataKey::Owner, &public_key);
+        let (credential_id, public_key, aaguid, data) = verify_attestation(
+            &env,
+            &attestation_object,
+            &client_data_json,
+            &allowed_origins,
+            &env.crypto().sha256(&rp_id).to_bytes(),
+            require_user_verification,
+            &attestation_config,
+        )?;
+        check_backup_policy(&data, backup_policy)?;
+        Self::init(
+            env.clone(),
+            public_key.into(),
+            credential_id.clone(),
+            allowed_origins,
+            rp_id,
+            require_user_verification,
+        )?;
 
-        // Optionally store credential ID
-        if let Some(cred_id) = credential_id {
-            env.storage().instance().set(&DataKey::CredentialId, &cred_id);
+        env.storage()
+            .instance()
+            .set(&DataKey::AttestationConfig, &attestation_config);
+        events::signer_attested(&env, &credential_id, &aaguid);
+        if backup_policy != BackupPolicy::Any {
+            store_backup_policy(&env, &credential_id, backup_policy);
         }
+        events::attestation_config_set(&env, &attestation_config);
 
         Ok(())
     }
 
-    /// Get the current owner's public key
-    pub fn get_owner(env: Env) -> Result<BytesN<64>, Error> {
+    // Get the credential IDs of all registered passkeys
+    pub fn list_signers(env: Env) -> Result<Vec<Bytes>, Error> {
         env.storage()
             .instance()
-            .get(&DataKey::Owner)
+            .get(&DataKey::Signers)
             .ok_or(Error::NotInitialized)
     }
 
-    /// Get the credential ID (if set)
+    // Get a registered passkey by credential ID
+    pub fn get_signer(env: Env, credential_id: Bytes) -> Result<Signer, Error> {
+        env.storage()
+            .instance()
+            .get(&DataKey::Signer(credential_id))
+            .ok_or(Error::SignerNotFound)
+    }
+
+    // Get the public key of the first registered passkey
+    //
+    // Deprecated: kept for clients of the single-passkey API, use `list_signers`
+    // and `get_signer`.
+    pub fn get_owner(env: Env) -> Result<BytesN<64>, Error> {
+        let credential_id = Self::get_credential_id(env.clone()).ok_or(Error::NotInitialized)?;
+        Ok(Self::get_signer(env, credential_id)?.public_key)
+    }
+
+    // Get the credential ID of the first registered passkey, if initialized
+    //
+    // Deprecated: kept for clients of the single-passkey API, use `list_signers`.
     pub fn get_credential_id(env: Env) -> Option<Bytes> {
-        env.storage().instance().get(&DataKey::CredentialId)
+        Self::list_signers(env).ok()?.first()
     }
 
-    /// Update the owner's public key (requires current owner's auth)
+    // Replace every passkey with a single one (requires the account's auth)
+    //
+    // Deprecated: kept for clients of the single-passkey API, use `add_signer` and
+    // `remove_signer`. The new passkey keeps the first passkey's credential ID unless
+    // one is given, and becomes the only signer with weight 1 and a threshold of 1;
+    // the replaced passkeys' policies are removed. Publishes `owner_updated`.
+    //
+    // # Errors
+    // * `AttestationRequired` - If the account requires attested passkeys
     pub fn update_owner(
         env: Env,
         new_public_key: BytesN<64>,
         new_credential_id: Option<Bytes>,
     ) -> Result<(), Error> {
-        // This will call __check_auth to verify the current owner
-        let _current_owner = Self::get_owner(env.clone())?;
+        // This will call __check_auth to verify the current signers
+        let current_credential_id =
+            Self::get_credential_id(env.clone()).ok_or(Error::NotInitialized)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        if env.storage().instance().has(&DataKey::AttestationConfig) {
+            return Err(Error::AttestationRequired);
+        }
+
+        let public_key = normalize_public_key(&env, &new_public_key.into())?;
+        let credential_id = new_credential_id.unwrap_or(current_credential_id);
+        clear_signers(&env)?;
+        store_new_signer(&env, credential_id.clone(), public_key.clone(), 1)?;
+        env.storage().instance().set(&DataKey::Threshold, &1u32);
+        events::owner_updated(&env, &credential_id, &public_key);
+
+        Ok(())
+    }
+
+    // Register another passkey, e.g. a second device (requires the account's auth)
+    //
+    // The public key may be the raw X || Y coordinates (64 bytes) or the uncompressed
+    // SEC1 point (65 bytes); it is stored as X || Y. It is not checked to be on the
+    // curve, which leaves a passkey that can never sign: clients decompress and
+    // validate other encodings first, as the factory does.
+    //
+    // # Errors
+    // * `AttestationRequired` - If the account requires attested passkeys
+    // * `InvalidPublicKey` - If the public key is not 64 or 65 uncompressed bytes
+    // * `SignerAlreadyExists` - If the credential ID is already registered
+    // * `PublicKeyAlreadyRegistered` - If the public key is already registered
+    // * `InvalidWeight` - If the weight is 0
+    pub fn add_signer(
+        env: Env,
+        credential_id: Bytes,
+        public_key: Bytes,
+        weight: u32,
+    ) -> Result<(), Error> {
+        // This will call __check_auth to verify the current signers
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        if env.storage().instance().has(&DataKey::AttestationConfig) {
+            return Err(Error::AttestationRequired);
+        }
+
+        let public_key = normalize_public_key(&env, &public_key)?;
+        store_new_signer(&env, credential_id.clone(), public_key.clone(), weight)?;
+        events::signer_added(&env, &credential_id, &public_key, weight);
+
+        Ok(())
+    }
+
+    // Register another passkey from its attestation (requires the account's auth)
+    //
+    // The `packed` attestation must verify and name an authenticator allowed by the
+    // account's attestation configuration; without one, no authenticator is trusted
+    // and every attestation is rejected. The passkey must also satisfy
+    // `backup_policy`, which is enforced on its assertions from then on.
+    //
+    // # Errors
+    // * `InvalidAttestation` - If the attestation is malformed or not `packed` ES256
+    // * `AttestationNotAllowed` - If the authenticator is not allowed
+    // * `BackupStateNotAllowed` - If the passkey does not satisfy `backup_policy`
+    // * `SignerAlreadyExists` - If the credential ID is already registered
+    // * `PublicKeyAlreadyRegistered` - If the public key is already registered
+    // * `InvalidWeight` - If the weight is 0
+    // * Errors of clientDataJSON and authenticator data checks, as in `__check_auth`
+    pub fn add_signer_attested(
+        env: Env,
+        attestation_object: Bytes,
+        client_data_json: Bytes,
+        weight: u32,
+        backup_policy: BackupPolicy,
+    ) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        let config =
+            Self::get_attestation_config(env.clone()).ok_or(Error::AttestationNotAllowed)?;
+        let rp_id_hash: BytesN<32> = env
+            .storage()
+            .instance()
+            .get(&DataKey::RpIdHash)
+            .ok_or(Error::NotInitialized)?;
+        let (credential_id, public_key, aaguid, data) = verify_attestation(
+            &env,
+            &attestation_object,
+            &client_data_json,
+            &Self::get_allowed_origins(env.clone()),
+            &rp_id_hash,
+            Self::get_user_verification_required(env.clone()),
+            &config,
+        )?;
+        check_backup_policy(&data, backup_policy)?;
+
+        store_new_signer(&env, credential_id.clone(), public_key.clone(), weight)?;
+        events::signer_added(&env, &credential_id, &public_key, weight);
+        events::signer_attested(&env, &credential_id, &aaguid);
+        if backup_policy != BackupPolicy::Any {
+            store_backup_policy(&env, &credential_id, backup_policy);
+        }
+
+        Ok(())
+    }
+
+    // Remove a registered passkey (requires the account's auth)
+    //
+    // # Errors
+    // * `SignerNotFound` - If the credential ID is not registered
+    // * `LastSigner` - If this is the only remaining passkey
+    // * `InvalidThreshold` - If the remaining signers could not reach the threshold
+    pub fn remove_signer(env: Env, credential_id: Bytes) -> Result<(), Error> {
+        let mut signers = Self::list_signers(env.clone())?;
         env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        let index = signers
+            .first_index_of(&credential_id)
+            .ok_or(Error::SignerNotFound)?;
+        if signers.len() == 1 {
+            return Err(Error::LastSigner);
+        }
+
+        // The remaining signers must still be able to reach the threshold
+        let signer = Self::get_signer(env.clone(), credential_id.clone())?;
+        let remaining_weight = total_weight(&env)? - u64::from(signer.weight);
+        if remaining_weight < u64::from(Self::get_threshold(env.clone())?) {
+            return Err(Error::InvalidThreshold);
+        }
+
+        signers.remove(index);
+        env.storage().instance().set(&DataKey::Signers, &signers);
+        env.storage()
+            .instance()
+            .remove(&DataKey::Signer(credential_id.clone()));
+        env.storage()
+            .instance()
+            .remove(&DataKey::SignerPolicy(credential_id.clone()));
+        env.storage()
+            .instance()
+            .remove(&DataKey::BackupPolicy(credential_id.clone()));
+        env.storage()
+            .instance()
+            .remove(&DataKey::CloneReport(credential_id.clone()));
+        events::signer_removed(&env, &credential_id);
 
-        // Update the public key
+        Ok(())
+    }
+
+    // Get the evidence that a passkey was cloned, if any was reported
+    pub fn get_clone_report(env: Env, credential_id: Bytes) -> Option<CloneReport> {
         env.storage()
             .instance()
-            .set(&DataKey::Owner, &new_public_key);
+            .get(&DataKey::CloneReport(credential_id))
+    }
 
-        // Update credential ID if provided
-        if let Some(cred_id) = new_credential_id {
-            env.storage().instance().set(&DataKey::CredentialId, &cred_id);
+    // Check a passkey's signature counter and record it if it did not increase
+    //
+    // `__check_auth` rejects such assertions, which rolls back anything it would
+    // record, so a possible clone is reported here instead. Anyone may submit the
+    // assertion, which must have `sha256("report_clone" || account address XDR ||
+    // credential ID || stored counter as 4 big-endian bytes)` as its challenge: only
+    // the passkey or its clone can produce it, and it goes stale once the counter moves.
+    // A counter that increased is recorded like an authorization, and 0 is ignored for
+    // authenticators that do not implement counters.
+    //
+    // # Returns
+    // * `true` if a clone was reported, publishing `clone_detected`
+    //
+    // # Errors
+    // * Any error verifying the assertion in `__check_auth` would return
+    pub fn report_clone(env: Env, assertion: Signature) -> Result<bool, Error> {
+        let credential_id = assertion.credential_id.clone();
+        let stored_sign_count = Self::get_signer(env.clone(), credential_id.clone())?.sign_count;
+        extend_instance_ttl(&env);
+
+        let mut message = credential_id.clone();
+        message.extend_from_array(&stored_sign_count.to_be_bytes());
+        let challenge = account_challenge(&env, "report_clone", &message);
+        let allowed_origins = Self::get_allowed_origins(env.clone());
+        match verify_assertion(&env, &challenge, &assertion, &allowed_origins, true) {
+            Ok(_) => Ok(false),
+            Err(Error::SignCountNotIncreased) => {
+                let sign_count =
+                    verify_authenticator_data(&env, &assertion.authenticator_data)?.sign_count;
+                let report = CloneReport {
+                    stored_sign_count,
+                    sign_count,
+                    ledger: env.ledger().sequence(),
+                };
+                env.storage()
+                    .instance()
+                    .set(&DataKey::CloneReport(credential_id.clone()), &report);
+                events::clone_detected(&env, &credential_id, stored_sign_count, sign_count);
+                Ok(true)
+            }
+            Err(error) => Err(error),
         }
+    }
+
+    // Get the rules restricting what a passkey may authorize, if any
+    pub fn get_signer_policy(env: Env, credential_id: Bytes) -> Option<Policy> {
+        env.storage()
+            .instance()
+            .get(&DataKey::SignerPolicy(credential_id))
+    }
+
+    // Restrict the contracts, functions and amounts a passkey may authorize
+    // (requires the account's auth)
+    //
+    // A call is only authorized if the signers whose policies allow it reach the
+    // threshold on their own. Signers without a policy are unrestricted.
+    //
+    // # Errors
+    // * `SignerNotFound` - If the credential ID is not registered
+    pub fn set_signer_policy(env: Env, credential_id: Bytes, policy: Policy) -> Result<(), Error> {
+        Self::get_signer(env.clone(), credential_id.clone())?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        env.storage()
+            .instance()
+            .set(&DataKey::SignerPolicy(credential_id.clone()), &policy);
+        events::signer_policy_set(&env, &credential_id, &policy);
 
         Ok(())
     }
-}
 
-/// Implementation of CustomAccountInterface for Soroban authentication
-#[contractimpl]
-impl CustomAccountInterface for PasskeyAccount {
-    type Signature = BytesN<64>;
-    type Error = Error;
+    // Lift the restrictions on a passkey (requires the account's auth)
+    //
+    // # Errors
+    // * `SignerNotFound` - If the credential ID is not registered
+    pub fn remove_signer_policy(env: Env, credential_id: Bytes) -> Result<(), Error> {
+        Self::get_signer(env.clone(), credential_id.clone())?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
 
-    /// The authentication entry point
-    /// 
-    /// This function is called by the Soroban host when `require_auth()` is invoked
-    /// on the contract's address. It verifies the secp256r1 signature from a passkey.
-    /// 
-    /// # Arguments
-    /// * `signature_payload` - The payload that was signed (32-byte hash)
-    /// * `signature` - The secp256r1 signature (64 bytes: r || s)
-    /// * `_auth_context` - Authorization context (not used in this simple example)
-    /// 
-    /// # Returns
-    /// * `Ok(())` if the signature is valid
-    /// * `Err(Error)` if verification fails
-    #[allow(non_snake_case)]
-    fn __check_auth(
+        env.storage()
+            .instance()
+            .remove(&DataKey::SignerPolicy(credential_id.clone()));
+        events::signer_policy_removed(&env, &credential_id);
+
+        Ok(())
+    }
+
+    // Get whether a passkey must be device-bound or synced (`Any` if not set)
+    pub fn get_backup_policy(env: Env, credential_id: Bytes) -> BackupPolicy {
+        env.storage()
+            .instance()
+            .get(&DataKey::BackupPolicy(credential_id))
+            .unwrap_or(BackupPolicy::Any)
+    }
+
+    // Require a passkey to be device-bound or synced (requires the account's auth)
+    //
+    // Enforced on every assertion: a passkey that does not satisfy the policy can no
+    // longer sign. Passkeys registered with `add_signer` carry no authenticator data,
+    // so their backup state is first checked on their next assertion.
+    //
+    // # Errors
+    // * `SignerNotFound` - If the credential ID is not registered
+    pub fn set_backup_policy(
         env: Env,
-        _signature_payload: Hash<32>,
-        signature: Self::Signature,
-        _auth_context: Vec<Context>,
-    ) -> Result<(), Self::Error> {
-        // Get the stored public key
-        let _public_key: BytesN<64> = env
-            .storage()
+        credential_id: Bytes,
+        policy: BackupPolicy,
+    ) -> Result<(), Error> {
+        Self::get_signer(env.clone(), credential_id.clone())?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        store_backup_policy(&env, &credential_id, policy);
+
+        Ok(())
+    }
+
+    // Get the total signer weight required to authorize an operation
+    pub fn get_threshold(env: Env) -> Result<u32, Error> {
+        env.storage()
             .instance()
-            .get(&DataKey::Owner)
-            .ok_or(Error::NotInitialized)?;
+            .get(&DataKey::Threshold)
+            .ok_or(Error::NotInitialized)
+    }
+
+    // Set the total signer weight required to authorize an operation (requires the
+    // account's auth)
+    //
+    // # Errors
+    // * `InvalidThreshold` - If the threshold is 0 or above the signers' total weight
+    pub fn set_threshold(env: Env, threshold: u32) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
 
-        // Note: In a production implementation with secp256r1 support, you would:
-        // 1. Use secp256r1_verify to verify the signature against the stored public key
-        // 2. Verify the authenticator data and client data from WebAuthn
-        // 3. Check the signature format matches ES256 (secp256r1)
-        
-        // For this demo, we're implementing a simplified version
-        // In production, you need to use a crypto library that supports secp256r1
-        // or wait for Soroban to add native secp256r1_verify support
-        
-        // Basic validation: check signature length
-        if signature.len() != 64 {
-            return Err(Error::InvalidSignature);
+        if threshold == 0 || u64::from(threshold) > total_weight(&env)? {
+            return Err(Error::InvalidThreshold);
         }
-        
-        // TODO: Implement proper secp256r1 signature verification
-        // This is a placeholder that accepts any 64-byte signature
-        // In production, you MUST implement proper cryptographic verification
-        
+        env.storage()
+            .instance()
+            .set(&DataKey::Threshold, &threshold);
+        events::threshold_changed(&env, threshold);
+
         Ok(())
     }
-}
 
-// Disable original passkey tests when running loan tests
-#[cfg(all(test, not(feature = "loan")))]
-mod test;
+    // Get the origins assertions are accepted from
+    pub fn get_allowed_origins(env: Env) -> Vec<Bytes> {
+        env.storage()
+            .instance()
+            .get(&DataKey::AllowedOrigins)
+            .unwrap_or(Vec::new(&env))
+    }
+
+    // Allow assertions from another origin (requires the account's auth)
+    pub fn add_allowed_origin(env: Env, origin: Bytes) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        let mut origins = Self::get_allowed_origins(env.clone());
+        if !origins.contains(&origin) {
+            origins.push_back(origin.clone());
+            env.storage()
+                .instance()
+                .set(&DataKey::AllowedOrigins, &origins);
+            events::origin_added(&env, &origin);
+        }
+
+        Ok(())
+    }
+
+    // Stop accepting assertions from an origin (requires the account's auth)
+    //
+    // # Errors
+    // * `EmptyOriginAllowList` - If this is the last allowed origin
+    pub fn remove_allowed_origin(env: Env, origin: Bytes) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        let mut origins = Self::get_allowed_origins(env.clone());
+        if let Some(index) = origins.first_index_of(&origin) {
+            if origins.len() == 1 {
+                return Err(Error::EmptyOriginAllowList);
+            }
+            origins.remove(index);
+            env.storage()
+                .instance()
+                .set(&DataKey::AllowedOrigins, &origins);
+            events::origin_removed(&env, &origin);
+        }
+
+        Ok(())
+    }
+
+    // Whether assertions must carry the User Verified (biometric/PIN) flag
+    pub fn get_user_verification_required(env: Env) -> bool {
+        env.storage()
+            .instance()
+            .get(&DataKey::RequireUserVerification)
+            .unwrap_or(false)
+    }
+
+    // Require (or stop requiring) user verification (requires the account's auth)
+    pub fn set_user_verification_required(env: Env, required: bool) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        env.storage()
+            .instance()
+            .set(&DataKey::RequireUserVerification, &required);
+        events::user_verification_set(&env, required);
+
+        Ok(())
+    }
+
+    // Get the authenticators new passkeys must be attested by, if attestation is required
+    pub fn get_attestation_config(env: Env) -> Option<AttestationConfig> {
+        env.storage().instance().get(&DataKey::AttestationConfig)
+    }
+
+    // Require new passkeys to be attested by the given authenticators (requires the
+    // account's auth)
+    //
+    // Passkeys already registered, and those installed by a recovery, are not affected.
+    pub fn set_attestation_config(env: Env, config: AttestationConfig) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        env.storage()
+            .instance()
+            .set(&DataKey::AttestationConfig, &config);
+        events::attestation_config_set(&env, &config);
+
+        Ok(())
+    }
+
+    // Stop requiring attestation for new passkeys (requires the account's auth)
+    pub fn remove_attestation_config(env: Env) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        env.storage().instance().remove(&DataKey::AttestationConfig);
+        events::attestation_config_removed(&env);
+
+        Ok(())
+    }
+
+    // Get the guardians that can recover the account, if any
+    pub fn get_recovery_config(env: Env) -> Option<RecoveryConfig> {
+        env.storage().instance().get(&DataKey::RecoveryConfig)
+    }
+
+    // Designate the guardians, their quorum and the recovery delay (requires the
+    // account's auth)
+    //
+    // Replacing the guardians cancels any pending recovery.
+    //
+    // # Errors
+    // * `InvalidGuardians` - If a guardian is listed twice, the quorum is out of range or
+    //   the delay is shorter than 24 hours
+    pub fn set_recovery_config(env: Env, config: RecoveryConfig) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        if config.quorum == 0 || config.quorum > config.guardians.len() {
+            return Err(Error::InvalidGuardians);
+        }
+        for (index, guardian) in config.guardians.iter().enumerate() {
+            if config.guardians.first_index_of(&guardian) != Some(index as u32) {
+                return Err(Error::InvalidGuardians);
+            }
+        }
+        if config.delay < MIN_RECOVERY_DELAY {
+            return Err(Error::InvalidGuardians);
+        }
+
+        env.storage()
+            .instance()
+            .set(&DataKey::RecoveryConfig, &config);
+        env.storage().instance().remove(&DataKey::Recovery);
+        events::recovery_config_set(&env, &config);
+
+        Ok(())
+    }
+
+    // Get the pending recovery, if any
+    pub fn get_recovery(env: Env) -> Option<Recovery> {
+        env.storage().instance().get(&DataKey::Recovery)
+    }
+
+    // Start replacing every passkey with a new one (requires the guardian's auth)
+    //
+    // The starting guardian's approval is recorded, and the replacement can be
+    // executed once a quorum approved it and the recovery delay has passed.
+    //
+    // # Errors
+    // * `NotGuardian` - If the address is not a guardian
+    // * `RecoveryInProgress` - If a recovery is already pending
+    // * `InvalidPublicKey` - If the public key is not 64 or 65 uncompressed bytes
+    pub fn start_recovery(
+        env: Env,
+        guardian: Address,
+        credential_id: Bytes,
+        public_key: Bytes,
+    ) -> Result<(), Error> {
+        let config = require_guardian(&env, &guardian)?;
+        extend_instance_ttl(&env);
+        if env.storage().instance().has(&DataKey::Recovery) {
+            return Err(Error::RecoveryInProgress);
+        }
+        let public_key = normalize_public_key(&env, &public_key)?;
+
+        let recovery = Recovery {
+            credential_id: credential_id.clone(),
+            public_key,
+            approvals: soroban_sdk::vec![&env, guardian.clone()],
+            execute_after: env.ledger().timestamp().saturating_add(config.delay),
+        };
+        env.storage().instance().set(&DataKey::Recovery, &recovery);
+        events::recovery_started(&env, &guardian, &credential_id, recovery.execute_after);
+
+        Ok(())
+    }
+
+    // Approve the pending recovery (requires the guardian's auth)
+    //
+    // The key is repeated so an approval cannot be applied to a different
+    // recovery started after a cancellation.
+    //
+    // # Errors
+    // * `NotGuardian` - If the address is not a guardian
+    // * `NoRecoveryInProgress` - If no recovery is pending
+    // * `RecoveryMismatch` - If the pending recovery is for a different key
+    // * `InvalidPublicKey` - If the public key is not 64 or 65 uncompressed bytes
+    pub fn approve_recovery(
+        env: Env,
+        guardian: Address,
+        credential_id: Bytes,
+        public_key: Bytes,
+    ) -> Result<(), Error> {
+        require_guardian(&env, &guardian)?;
+        extend_instance_ttl(&env);
+        let mut recovery = Self::get_recovery(env.clone()).ok_or(Error::NoRecoveryInProgress)?;
+        // Any encoding of the same key approves it
+        let public_key = normalize_public_key(&env, &public_key)?;
+        if recovery.credential_id != credential_id || recovery.public_key != public_key {
+            return Err(Error::RecoveryMismatch);
+        }
+
+        if !recovery.approvals.contains(&guardian) {
+            recovery.approvals.push_back(guardian.clone());
+            env.storage().instance().set(&DataKey::Recovery, &recovery);
+        }
+        events::recovery_approved(&env, &guardian, recovery.approvals.len());
+
+        Ok(())
+    }
+
+    // Cancel the pending recovery (requires the account's auth)
+    //
+    // Any single signer can authorize this, regardless of the threshold, so that
+    // guardians cannot take over an account whose passkeys are not actually lost.
+    //
+    // # Errors
+    // * `NoRecoveryInProgress` - If no recovery is pending
+    pub fn cancel_recovery(env: Env) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        let recovery = Self::get_recovery(env.clone()).ok_or(Error::NoRecoveryInProgress)?;
+        env.storage().instance().remove(&DataKey::Recovery);
+        events::recovery_cancelled(&env, &recovery.credential_id);
+
+        Ok(())
+    }
+
+    // Replace every passkey with the recovered one once a quorum approved it and
+    // the recovery delay has passed (callable by anyone)
+    //
+    // The recovered passkey becomes the only signer, with weight 1 and a threshold of 1,
+    // and every session key is revoked.
+    //
+    // # Errors
+    // * `NoRecoveryInProgress` - If no recovery is pending
+    // * `RecoveryNotReady` - If approvals are missing or the delay has not passed
+    pub fn execute_recovery(env: Env) -> Result<(), Error> {
+        let recovery = Self::get_recovery(env.clone()).ok_or(Error::NoRecoveryInProgress)?;
+        let config = Self::get_recovery_config(env.clone()).ok_or(Error::NoRecoveryInProgress)?;
+        if recovery.approvals.len() < config.quorum
+            || env.ledger().timestamp() < recovery.execute_after
+        {
+            return Err(Error::RecoveryNotReady);
+        }
+        extend_instance_ttl(&env);
+
+        clear_signers(&env)?;
+        store_new_signer(
+            &env,
+            recovery.credential_id.clone(),
+            recovery.public_key.clone(),
+            1,
+        )?;
+        env.storage().instance().set(&DataKey::Threshold, &1u32);
+        env.storage().instance().remove(&DataKey::Recovery);
+
+        // Session keys may have been registered by whoever holds the lost passkeys
+        for public_key in session_key_ids(&env).iter() {
+            env.storage()
+                .instance()
+                .remove(&DataKey::SessionKey(public_key.clone()));
+            events::session_key_revoked(&env, &public_key);
+        }
+        env.storage().instance().remove(&DataKey::SessionKeys);
+
+        events::recovery_completed(&env, &recovery.credential_id, &recovery.public_key);
+
+        Ok(())
+    }
+
+    // Register a session key (requires the account's auth)
+    //
+    // Registering a key again replaces it and resets what it spent. Expired keys are
+    // removed.
+    //
+    // # Arguments
+    // * `public_key` - ed25519 or secp256r1 public key held by the dApp
+    // * `expiration_ledger` - Last ledger sequence the key is valid for
+    // * `allowed_calls` - Contracts (and optionally functions) the key may call
+    // * `spend_limits` - Maximum total amount of each token the key may `transfer` or
+    //   `approve`; tokens not listed are not capped
+    //
+    // # Errors
+    // * `InvalidSessionKey` - If the key is already expired, may call this account
+    //   (other than `is_valid_signature`, which lets it sign messages), or a spend
+    //   limit is negative
+    pub fn add_session_key(
+        env: Env,
+        public_key: SessionPublicKey,
+        expiration_ledger: u32,
+        allowed_calls: Vec<AllowedCall>,
+        spend_limits: Map<Address, i128>,
+    ) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        // A session key must never be able to manage the account itself; it may only
+        // be allowed to sign messages on its behalf
+        let account = env.current_contract_address();
+        let sign_messages = Vec::from_array(&env, [Symbol::new(&env, "is_valid_signature")]);
+        if expiration_ledger < env.ledger().sequence()
+            || allowed_calls
+                .iter()
+                .any(|call| call.contract == account && call.functions != sign_messages)
+            || spend_limits.values().iter().any(|limit| limit < 0)
+        {
+            return Err(Error::InvalidSessionKey);
+        }
+
+        let mut session_keys = prune_session_keys(&env);
+        if !session_keys.contains(&public_key) {
+            session_keys.push_back(public_key.clone());
+            env.storage()
+                .instance()
+                .set(&DataKey::SessionKeys, &session_keys);
+        }
+        env.storage().instance().set(
+            &DataKey::SessionKey(public_key.clone()),
+            &SessionKey {
+                public_key: public_key.clone(),
+                expiration_ledger,
+                allowed_calls,
+                spend_limits: spend_limits.clone(),
+                spent: Map::new(&env),
+            },
+        );
+        events::session_key_added(&env, &public_key, expiration_ledger, &spend_limits);
+
+        Ok(())
+    }
+
+    // Revoke a session key (requires the account's auth)
+    //
+    // # Errors
+    // * `SessionKeyNotFound` - If no session key is registered under the public key
+    pub fn revoke_session_key(env: Env, public_key: SessionPublicKey) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        let mut session_keys = session_key_ids(&env);
+        let index = session_keys
+            .first_index_of(&public_key)
+            .ok_or(Error::SessionKeyNotFound)?;
+        session_keys.remove(index);
+        env.storage()
+            .instance()
+            .set(&DataKey::SessionKeys, &session_keys);
+        env.storage()
+            .instance()
+            .remove(&DataKey::SessionKey(public_key.clone()));
+        events::session_key_revoked(&env, &public_key);
+
+        Ok(())
+    }
+
+    // Get the spending limit of a token, if any
+    pub fn get_spend_limit(env: Env, token: Address) -> Option<SpendLimit> {
+        env.storage().instance().get(&DataKey::SpendLimit(token))
+    }
+
+    // Limit how much of a token the account may `transfer` or `approve` in any
+    // 24-hour window (requires the account's auth)
+    //
+    // Spends are tracked by the hour and each counts for at least 24 hours. Passkey
+    // signatures whose total weight reaches `override_weight` may exceed the limit;
+    // session keys never can.
+    //
+    // # Errors
+    // * `InvalidSpendLimit` - If the limit is negative or the override weight is 0
+    pub fn set_spend_limit(env: Env, token: Address, spend_limit: SpendLimit) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        if spend_limit.limit < 0 || spend_limit.override_weight == 0 {
+            return Err(Error::InvalidSpendLimit);
+        }
+        env.storage()
+            .instance()
+            .set(&DataKey::SpendLimit(token.clone()), &spend_limit);
+        events::spend_limit_set(&env, &token, &spend_limit);
+
+        Ok(())
+    }
+
+    // Remove the spending limit of a token (requires the account's auth)
+    pub fn remove_spend_limit(env: Env, token: Address) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        env.storage()
+            .instance()
+            .remove(&DataKey::SpendLimit(token.clone()));
+        env.storage()
+            .persistent()
+            .remove(&DataKey::Spending(token.clone()));
+        events::spend_limit_removed(&env, &token);
+
+        Ok(())
+    }
+
+    // Get the amount of a token transferred or approved in the current spending window
+    pub fn get_spent(env: Env, token: Address) -> i128 {
+        recent_spending(&env, &token)
+            .iter()
+            .fold(0, |total, bucket| total.saturating_add(bucket.amount))
+    }
+
+    // Get the external policy contracts consulted by `__check_auth`
+    pub fn list_policies(env: Env) -> Vec<Address> {
+        env.storage()
+            .instance()
+            .get(&DataKey::PolicyContracts)
+            .unwrap_or(Vec::new(&env))
+    }
+
+    // Install an external policy contract (requires the account's auth)
+    //
+    // The policy's `install` is called so it can record the account, and every
+    // authorization is then passed to it after its signatures are verified, and is
+    // rejected if the policy fails.
+    //
+    // # Errors
+    // * `PolicyAlreadyInstalled` - If the policy is already installed
+    // * `PolicyRejected` - If the policy refuses to be installed
+    pub fn install_policy(env: Env, policy: Address) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        let mut policies = Self::list_policies(env.clone());
+        if policies.contains(&policy) {
+            return Err(Error::PolicyAlreadyInstalled);
+        }
+        let client = PolicyContractClient::new(&env, &policy);
+        if !matches!(
+            client.try_install(&env.current_contract_address()),
+            Ok(Ok(()))
+        ) {
+            return Err(Error::PolicyRejected);
+        }
+        policies.push_back(policy.clone());
+        env.storage()
+            .instance()
+            .set(&DataKey::PolicyContracts, &policies);
+        events::policy_installed(&env, &policy);
+
+        Ok(())
+    }
+
+    // Uninstall an external policy contract (requires the account's auth)
+    //
+    // Policies are not consulted when authorizing uninstalls, so a faulty policy
+    // cannot lock the account.
+    //
+    // # Errors
+    // * `PolicyNotInstalled` - If the policy is not installed
+    pub fn uninstall_policy(env: Env, policy: Address) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        let mut policies = Self::list_policies(env.clone());
+        let index = policies
+            .first_index_of(&policy)
+            .ok_or(Error::PolicyNotInstalled)?;
+        policies.remove(index);
+        env.storage()
+            .instance()
+            .set(&DataKey::PolicyContracts, &policies);
+        events::policy_uninstalled(&env, &policy);
+
+        Ok(())
+    }
+
+    // Get when and how far the account's TTL is extended
+    pub fn get_ttl_config(env: Env) -> TtlConfig {
+        env.storage()
+            .instance()
+            .get(&DataKey::TtlConfig)
+            .unwrap_or(DEFAULT_TTL_CONFIG)
+    }
+
+    // Change when and how far the account's TTL is extended (requires the account's auth)
+    //
+    // # Errors
+    // * `InvalidTtlConfig` - If the threshold is above the extension, or the extension
+    //   is above the network's maximum TTL
+    pub fn set_ttl_config(env: Env, config: TtlConfig) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+
+        if config.threshold > config.extend_to || config.extend_to > env.storage().max_ttl() {
+            return Err(Error::InvalidTtlConfig);
+        }
+        env.storage().instance().set(&DataKey::TtlConfig, &config);
+        extend_instance_ttl(&env);
+        events::ttl_config_set(&env, &config);
+
+        Ok(())
+    }
+
+    // Extend the account's TTL if it is below the threshold (callable by anyone)
+    //
+    // The TTL is also extended by every authorization and account change, so this is
+    // only needed for accounts that sit unused.
+    pub fn extend_ttl(env: Env) -> Result<(), Error> {
+        require_initialized(&env)?;
+        extend_instance_ttl(&env);
+        Ok(())
+    }
+
+    // Get the version of the stored data (0 for accounts created before versioning)
+    pub fn version(env: Env) -> u32 {
+        env.storage().instance().get(&DataKey::Version).unwrap_or(0)
+    }
+
+    // Bring the stored data up to this code's version after an upgrade (callable by
+    // anyone, does nothing if already current)
+    //
+    // # Errors
+    // * `InvalidVersion` - If the data is from a newer version (a downgrade)
+    pub fn migrate(env: Env) -> Result<(), Error> {
+        require_initialized(&env)?;
+
+        let version = Self::version(env.clone());
+        if version > VERSION {
+            return Err(Error::InvalidVersion);
+        }
+        if version == VERSION {
+            return Ok(());
+        }
+
+        // Storage layout changes add a step per version here, e.g. `if version < 2 { .. }`
+        env.storage().instance().set(&DataKey::Version, &VERSION);
+        extend_instance_ttl(&env);
+        events::migrated(&env, version, VERSION);
+
+        Ok(())
+    }
+
+    // Get the number of seconds an upgrade must wait before it can be applied
+    pub fn get_upgrade_delay(env: Env) -> u64 {
+        env.storage()
+            .instance()
+            .get(&DataKey::UpgradeDelay)
+            .unwrap_or(0)
+    }
+
+    // Make upgrades wait before they can be applied (requires the account's auth)
+    //
+    // # Errors
+    // * `InvalidUpgradeDelay` - If the delay is shorter than the current one, so that
+    //   whoever controls the account cannot shorten the window to cancel an upgrade
+    pub fn set_upgrade_delay(env: Env, delay: u64) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        if delay < Self::get_upgrade_delay(env.clone()) {
+            return Err(Error::InvalidUpgradeDelay);
+        }
+        env.storage().instance().set(&DataKey::UpgradeDelay, &delay);
+        events::upgrade_delay_set(&env, delay);
+
+        Ok(())
+    }
+
+    // Get the upgrade waiting for its delay to pass, if any
+    pub fn get_pending_upgrade(env: Env) -> Option<PendingUpgrade> {
+        env.storage().instance().get(&DataKey::PendingUpgrade)
+    }
+
+    // Upgrade the account to uploaded WASM (requires the account's auth)
+    //
+    // Without an upgrade delay the new code takes over immediately; otherwise the
+    // upgrade is scheduled and applied with `apply_upgrade` once the delay has passed.
+    // Either way, call `migrate` afterwards.
+    //
+    // # Errors
+    // * `UpgradeInProgress` - If an upgrade is already pending
+    pub fn upgrade(env: Env, new_wasm_hash: BytesN<32>) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        if env.storage().instance().has(&DataKey::PendingUpgrade) {
+            return Err(Error::UpgradeInProgress);
+        }
+
+        let delay = Self::get_upgrade_delay(env.clone());
+        if delay == 0 {
+            update_wasm(&env, new_wasm_hash);
+            return Ok(());
+        }
+
+        let execute_after = env.ledger().timestamp().saturating_add(delay);
+        env.storage().instance().set(
+            &DataKey::PendingUpgrade,
+            &PendingUpgrade {
+                wasm_hash: new_wasm_hash.clone(),
+                execute_after,
+            },
+        );
+        events::upgrade_scheduled(&env, &new_wasm_hash, execute_after);
+
+        Ok(())
+    }
+
+    // Apply the pending upgrade once its delay has passed (callable by anyone)
+    //
+    // # Errors
+    // * `NoUpgradePending` - If no upgrade is pending
+    // * `UpgradeNotReady` - If the delay has not passed
+    pub fn apply_upgrade(env: Env) -> Result<(), Error> {
+        let pending = Self::get_pending_upgrade(env.clone()).ok_or(Error::NoUpgradePending)?;
+        if env.ledger().timestamp() < pending.execute_after {
+            return Err(Error::UpgradeNotReady);
+        }
+        extend_instance_ttl(&env);
+
+        env.storage().instance().remove(&DataKey::PendingUpgrade);
+        update_wasm(&env, pending.wasm_hash);
+
+        Ok(())
+    }
+
+    // Cancel the pending upgrade (requires the account's auth)
+    //
+    // Like cancelling a recovery, any single signer can authorize this.
+    //
+    // # Errors
+    // * `NoUpgradePending` - If no upgrade is pending
+    pub fn cancel_upgrade(env: Env) -> Result<(), Error> {
+        require_initialized(&env)?;
+        env.current_contract_address().require_auth();
+        extend_instance_ttl(&env);
+
+        let pending = Self::get_pending_upgrade(env.clone()).ok_or(Error::NoUpgradePending)?;
+        env.storage().instance().remove(&DataKey::PendingUpgrade);
+        events::upgrade_cancelled(&env, &pending.wasm_hash);
+
+        Ok(())
+    }
+
+    // Get the session keys that have not expired
+    pub fn list_session_keys(env: Env) -> Vec<SessionKey> {
+        let mut active = Vec::new(&env);
+        for public_key in session_key_ids(&env).iter() {
+            if let Some(session_key) = get_session_key(&env, &public_key) {
+                if !session_key.is_expired(&env) {
+                    active.push_back(session_key);
+                }
+            }
+        }
+        active
+    }
+
+    // Check a signature over an off-chain message hash
+    //
+    // Accepts the same authorizations as `__check_auth`, verified the same way, for a
+    // call to this function with `hash` as its only argument. What is signed is not
+    // `hash` but `sha256("is_valid_signature" || account address XDR || hash)`, so a
+    // message signature can neither be replayed as a transaction authorization nor on
+    // another account with the same signers: passkey assertions must have it as their
+    // challenge and reach the threshold, and session keys sign it (secp256r1 keys hash
+    // it with SHA-256). Signers whose policies do not allow the call, and session keys
+    // not allowed to make it, cannot sign.
+    //
+    // Nothing is written, so signature counters are not checked, and installed policy
+    // contracts are skipped: their `enforce` may keep state, and a message signature
+    // moves no funds for them to control.
+    //
+    // # Errors
+    // * Any error `__check_auth` would return for the same authorization
+    pub fn is_valid_signature(
+        env: Env,
+        hash: BytesN<32>,
+        signature: Authorization,
+    ) -> Result<(), Error> {
+        require_initialized(&env)?;
+
+        let call = Context::Contract(ContractContext {
+            contract: env.current_contract_address(),
+            fn_name: Symbol::new(&env, "is_valid_signature"),
+            args: Vec::from_array(&env, [hash.into_val(&env)]),
+        });
+        let challenge = account_challenge(&env, "is_valid_signature", &hash.into());
+        let digest = env.crypto().sha256(&challenge.clone().into());
+        authorize(
+            &env,
+            &challenge,
+            &digest,
+            signature,
+            &Vec::from_array(&env, [call]),
+            false,
+        )
+    }
+}
+
+/// Implementation of CustomAccountInterface for Soroban authentication
+#[cfg_attr(
+    not(any(feature = "factory", feature = "loan", feature = "attestation")),
+    soroban_sdk::contractimpl
+)]
+impl CustomAccountInterface for PasskeyAccount {
+    type Signature = Authorization;
+    type Error = Error;
+
+    // The authentication entry point
+    //
+    // This function is called by the Soroban host when `require_auth()` is invoked
+    // on the contract's address. Each signature is a WebAuthn assertion from a
+    // registered passkey: the clientDataJSON challenge must be the signature payload,
+    // and the secp256r1 signature must cover `authenticatorData || sha256(clientDataJSON)`.
+    // The weights of the signing passkeys are summed and must reach the threshold,
+    // and for each call, the weights of the signers whose policies allow it must reach
+    // the threshold too; cancelling a pending recovery or upgrade only needs one signer.
+    // Alternatively, a session key may sign calls within its scope until it expires.
+    //
+    // # Arguments
+    // * `signature_payload` - The payload that was signed (32-byte hash)
+    // * `authorization` - WebAuthn assertions (at most one per passkey) or a session
+    //   key signature
+    // * `auth_context` - Authorization context (the calls being authorized)
+    //
+    // # Returns
+    // * `Ok(())` if the signatures are valid and their total weight reaches the threshold,
+    //   or the session key signature is valid and in scope
+    // * `Err(Error)` if verification fails
+    //
+    // Malformed signatures are rejected with `InvalidSignature`. A well-formed
+    // signature that does not verify makes the host's `secp256r1_verify` trap,
+    // which also fails the authorization.
+    #[allow(non_snake_case)]
+    fn __check_auth(
+        env: Env,
+        signature_payload: Hash<32>,
+        authorization: Self::Signature,
+        auth_context: Vec<Context>,
+    ) -> Result<(), Self::Error> {
+        // Keep an account in use from being archived
+        require_initialized(&env)?;
+        extend_instance_ttl(&env);
+
+        let payload = signature_payload.to_bytes();
+        authorize(
+            &env,
+            &payload,
+            &signature_payload,
+            authorization,
+            &auth_context,
+            true,
+        )
+    }
+}
+
+/// `sha256(domain || account address XDR || message)`, the challenge a passkey signs
+/// for an account outside of `__check_auth`
+fn account_challenge(env: &Env, domain: &str, message: &Bytes) -> BytesN<32> {
+    let mut preimage = Bytes::from_slice(env, domain.as_bytes());
+    preimage.append(&env.current_contract_address().to_xdr(env));
+    preimage.append(message);
+    env.crypto().sha256(&preimage).to_bytes()
+}
+
+/// Checks an authorization of the calls in `auth_context`
+///
+/// Passkey assertions must have `payload` as their challenge; session keys sign
+/// `payload` (ed25519) or `digest` (secp256r1). With `record` unset nothing is written:
+/// signature counters, session key spending and spend limits are left untouched, and
+/// policy contracts, which may keep state, are not consulted.
+fn authorize(
+    env: &Env,
+    payload: &BytesN<32>,
+    digest: &Hash<32>,
+    authorization: Authorization,
+    auth_context: &Vec<Context>,
+    record: bool,
+) -> Result<(), Error> {
+    let signatures = match authorization {
+        Authorization::Passkeys(signatures) => signatures,
+        Authorization::SessionKey(signature) => {
+            verify_session_key(env, payload, digest, &signature, auth_context, record)?;
+            if !record {
+                return Ok(());
+            }
+            let authorizer = Authorizer::SessionKey(signature.public_key);
+            enforce_policy_contracts(env, &authorizer, auth_context)?;
+            return enforce_spend_limits(env, auth_context, 0);
+        }
+    };
+
+    let threshold = if is_only_call_to(env, auth_context, "cancel_recovery")
+        || is_only_call_to(env, auth_context, "cancel_upgrade")
+    {
+        1
+    } else {
+        PasskeyAccount::get_threshold(env.clone())?
+    };
+    let allowed_origins = PasskeyAccount::get_allowed_origins(env.clone());
+
+    // Weight of each passkey that signed
+    let mut signed = Map::<Bytes, u32>::new(env);
+    let mut signed_keys = Vec::<BytesN<64>>::new(env);
+    let mut weight: u64 = 0;
+    for signature in signatures.iter() {
+        // Each passkey counts once, however many assertions it produced
+        if signed.contains_key(signature.credential_id.clone()) {
+            return Err(Error::DuplicateSignature);
+        }
+
+        let signer = verify_assertion(env, payload, &signature, &allowed_origins, record)?;
+        // ...and however many credential IDs it was registered under
+        if signed_keys.contains(&signer.public_key) {
+            return Err(Error::DuplicateSignature);
+        }
+        signed_keys.push_back(signer.public_key);
+        signed.set(signature.credential_id.clone(), signer.weight);
+        weight += u64::from(signer.weight);
+    }
+
+    if weight < u64::from(threshold) {
+        return Err(Error::ThresholdNotMet);
+    }
+
+    // Restricted signers only count towards the calls their policies allow
+    for context in auth_context.iter() {
+        let mut allowed_weight: u64 = 0;
+        for (credential_id, signer_weight) in signed.iter() {
+            let allowed = PasskeyAccount::get_signer_policy(env.clone(), credential_id)
+                .is_none_or(|policy| policy.allows(env, &context));
+            if allowed {
+                allowed_weight += u64::from(signer_weight);
+            }
+        }
+        if allowed_weight < u64::from(threshold) {
+            return Err(Error::ContextNotAllowed);
+        }
+    }
+
+    if !record {
+        return Ok(());
+    }
+    let authorizer = Authorizer::Passkeys(signed.keys());
+    enforce_policy_contracts(env, &authorizer, auth_context)?;
+    enforce_spend_limits(env, auth_context, weight)
+}
+
+/// Order of the secp256r1 curve (n), big-endian
+const SECP256R1_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xbc, 0xe6, 0xfa, 0xad, 0xa7, 0x17, 0x9e, 0x84, 0xf3, 0xb9, 0xca, 0xc2, 0xfc, 0x63, 0x25, 0x51,
+];
+
+/// Half the order of the secp256r1 curve (n / 2), big-endian
+const SECP256R1_HALF_ORDER: [u8; 32] = [
+    0x7f, 0xff, 0xff, 0xff, 0x80, 0x00, 0x00, 0x00, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+    0xde, 0x73, 0x7d, 0x56, 0xd3, 0x8b, 0xcf, 0x42, 0x79, 0xdc, 0xe5, 0x61, 0x7e, 0x31, 0x92, 0xa8,
+];
+
+/// Copies input out of the host into `buffer`, or returns `None` if it does not fit
+fn load<'b, const N: usize>(input: &Bytes, buffer: &'b mut [u8; N]) -> Option<&'b [u8]> {
+    let input_buffer = buffer.get_mut(..input.len() as usize)?;
+    input.copy_into_slice(input_buffer);
+    Some(input_buffer)
+}
+
+/// Converts a raw `X || Y` public key into the SEC-1 uncompressed form (0x04 || X || Y)
+/// expected by the host
+fn sec1_uncompressed(env: &Env, public_key: &BytesN<64>) -> BytesN<65> {
+    let mut key = [0u8; 65];
+    key[0] = 0x04;
+    key[1..].copy_from_slice(&public_key.to_array());
+    BytesN::from_array(env, &key)
+}
+
+/// Checks that `r` is in `[1, n)` and `s` is in `[1, n/2]`
+fn is_valid_signature_encoding(signature: &[u8; 64]) -> bool {
+    let (r, s) = signature.split_at(32);
+    let is_zero = |scalar: &[u8]| scalar.iter().all(|b| *b == 0);
+
+    !is_zero(r) && !is_zero(s) && r < &SECP256R1_ORDER[..] && s <= &SECP256R1_HALF_ORDER[..]
+}
+
+/// Verifies a session key signature and that the key may authorize every call,
+/// recording what it spends if `record` is set
+fn verify_session_key(
+    env: &Env,
+    payload: &BytesN<32>,
+    digest: &Hash<32>,
+    signature: &SessionSignature,
+    auth_context: &Vec<Context>,
+    record: bool,
+) -> Result<(), Error> {
+    let mut session_key =
+        get_session_key(env, &signature.public_key).ok_or(Error::SessionKeyNotFound)?;
+    if session_key.is_expired(env) {
+        return Err(Error::SessionKeyExpired);
+    }
+
+    session::verify(env, payload, digest, signature)?;
+
+    if !session_key.allows(auth_context) {
+        return Err(Error::SessionKeyOutOfScope);
+    }
+    session_key.spend(env, auth_context)?;
+    if record {
+        env.storage().instance().set(
+            &DataKey::SessionKey(signature.public_key.clone()),
+            &session_key,
+        );
+    }
+
+    Ok(())
+}
+
+/// Verifies a single WebAuthn assertion and returns the passkey that signed
+///
+/// The signature counter is only checked and recorded if `record` is set.
+fn verify_assertion(
+    env: &Env,
+    challenge: &BytesN<32>,
+    signature: &Signature,
+    allowed_origins: &Vec<Bytes>,
+    record: bool,
+) -> Result<Signer, Error> {
+    // Look up the passkey that signed
+    let mut signer = PasskeyAccount::get_signer(env.clone(), signature.credential_id.clone())?;
+
+    // The host only accepts scalars in range with a low-S `s` component
+    if !is_valid_signature_encoding(&signature.signature.to_array()) {
+        return Err(Error::InvalidSignature);
+    }
+
+    // The assertion must be a `webauthn.get` for this exact payload from an allowed origin
+    verify_client_data(env, &signature.client_data_json, challenge, allowed_origins)?;
+
+    // The authenticator must have been used for this relying party with the user present
+    let authenticator_data = verify_authenticator_data(env, &signature.authenticator_data)?;
+
+    // Synced passkeys may be refused for this signer, or required
+    check_backup_policy(
+        &authenticator_data,
+        PasskeyAccount::get_backup_policy(env.clone(), signature.credential_id.clone()),
+    )?;
+
+    // Verify the signature with the signer's key
+    verify_signature(env, &signer.public_key, signature);
+
+    if record {
+        update_sign_count(
+            env,
+            &signature.credential_id,
+            &mut signer,
+            authenticator_data.sign_count,
+        )?;
+    }
+
+    Ok(signer)
+}
+
+/// Verifies the secp256r1 signature of an assertion, which covers
+/// `authenticatorData || sha256(clientDataJSON)`; the host traps if it does not verify
+fn verify_signature(env: &Env, public_key: &BytesN<64>, signature: &Signature) {
+    let mut message = signature.authenticator_data.clone();
+    message.extend_from_array(&env.crypto().sha256(&signature.client_data_json).to_array());
+    let digest = env.crypto().sha256(&message);
+    env.crypto().secp256r1_verify(
+        &sec1_uncompressed(env, public_key),
+        &digest,
+        &signature.signature,
+    );
+}
+
+/// Checks clientDataJSON: `type` must be `webauthn.get`, `origin` must be allowed and the
+/// base64url `challenge` must decode to the expected challenge
+fn verify_client_data(
+    env: &Env,
+    client_data_json: &Bytes,
+    expected_challenge: &BytesN<32>,
+    allowed_origins: &Vec<Bytes>,
+) -> Result<(), Error> {
+    let mut buffer = [0u8; client_data::MAX_CLIENT_DATA_LEN];
+    let client_data = load(client_data_json, &mut buffer).ok_or(Error::ClientDataTooLarge)?;
+    let client_data = client_data::parse(client_data)?;
+
+    if !client_data.is_get() {
+        return Err(Error::InvalidClientDataType);
+    }
+    if !client_data.origin_allowed(env, allowed_origins) {
+        return Err(Error::OriginNotAllowed);
+    }
+
+    // The strict decoder accepts a single encoding of each challenge
+    let mut encoded = [0u8; 64];
+    let challenge = client_data
+        .challenge
+        .unescape_into(&mut encoded)
+        .and_then(|encoded| base64_url::decode_32(encoded).ok())
+        .ok_or(Error::ChallengeMismatch)?;
+
+    if !constant_time_eq(&challenge, &expected_challenge.to_array()) {
+        return Err(Error::ChallengeMismatch);
+    }
+
+    Ok(())
+}
+
+/// Compares two 32-byte values in constant time
+fn constant_time_eq(a: &[u8; 32], b: &[u8; 32]) -> bool {
+    a.iter()
+        .zip(b.iter())
+        .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+        == 0
+}
+
+/// Checks the authenticator data header against the account's relying party and UP/UV policy
+fn verify_authenticator_data(
+    env: &Env,
+    authenticator_data: &Bytes,
+) -> Result<authenticator_data::AuthenticatorData, Error> {
+    let data = authenticator_data::parse(authenticator_data)
+        .map_err(|_| Error::InvalidAuthenticatorData)?;
+
+    let rp_id_hash: BytesN<32> = env
+        .storage()
+        .instance()
+        .get(&DataKey::RpIdHash)
+        .ok_or(Error::NotInitialized)?;
+    let require_user_verification = PasskeyAccount::get_user_verification_required(env.clone());
+    check_authenticator_data(&data, &rp_id_hash, require_user_verification)?;
+
+    Ok(data)
+}
+
+/// Checks the relying party and the UP/UV flags of authenticator data
+fn check_authenticator_data(
+    data: &authenticator_data::AuthenticatorData,
+    rp_id_hash: &BytesN<32>,
+    require_user_verification: bool,
+) -> Result<(), Error> {
+    if data.rp_id_hash != rp_id_hash.to_array() {
+        return Err(Error::RpIdHashMismatch);
+    }
+
+    if !data.user_present() {
+        return Err(Error::UserNotPresent);
+    }
+    if !data.user_verified() && require_user_verification {
+        return Err(Error::UserNotVerified);
+    }
+
+    // Only a backup eligible credential can be backed up
+    if data.backed_up() && !data.backup_eligible() {
+        return Err(Error::InvalidAuthenticatorData);
+    }
+
+    Ok(())
+}
+
+/// Checks the BE/BS flags of authenticator data against a signer's backup policy
+fn check_backup_policy(
+    data: &authenticator_data::AuthenticatorData,
+    policy: BackupPolicy,
+) -> Result<(), Error> {
+    let allowed = match policy {
+        BackupPolicy::Any => true,
+        BackupPolicy::DeviceBound => !data.backup_eligible(),
+        BackupPolicy::Synced => data.backed_up(),
+    };
+    if !allowed {
+        return Err(Error::BackupStateNotAllowed);
+    }
+
+    Ok(())
+}
+
+/// Verifies the attestation of a new passkey and returns its credential ID, public key,
+/// AAGUID and authenticator data header
+///
+/// clientDataJSON must be a `webauthn.create` from an allowed origin; its challenge is
+/// not checked, since registering a passkey already requires the account's auth.
+fn verify_attestation(
+    env: &Env,
+    attestation_object: &Bytes,
+    client_data_json: &Bytes,
+    allowed_origins: &Vec<Bytes>,
+    rp_id_hash: &BytesN<32>,
+    require_user_verification: bool,
+    config: &AttestationConfig,
+) -> Result<
+    (
+        Bytes,
+        BytesN<64>,
+        BytesN<16>,
+        authenticator_data::AuthenticatorData,
+    ),
+    Error,
+> {
+    let mut buffer = [0u8; client_data::MAX_CLIENT_DATA_LEN];
+    let client_data = load(client_data_json, &mut buffer).ok_or(Error::ClientDataTooLarge)?;
+    let client_data = client_data::parse(client_data)?;
+    if !client_data.is_create() {
+        return Err(Error::InvalidClientDataType);
+    }
+    if !client_data.origin_allowed(env, allowed_origins) {
+        return Err(Error::OriginNotAllowed);
+    }
+
+    // The verifier's own errors are passed on, and its failures reject the attestation
+    let client_data_hash = env.crypto().sha256(client_data_json).to_bytes();
+    let attestation = AttestationContractClient::new(env, &config.verifier)
+        .try_verify(attestation_object, &client_data_hash, &config.issuers)
+        .map_err(|error| error.unwrap_or(Error::InvalidAttestation))?
+        .map_err(|_| Error::InvalidAttestation)?;
+
+    let data = authenticator_data::parse(&attestation.authenticator_data)
+        .map_err(|_| Error::InvalidAuthenticatorData)?;
+    check_authenticator_data(&data, rp_id_hash, require_user_verification)?;
+
+    // Self attestation only proves the passkey holds its key, not what it runs on
+    if (attestation.self_attested && !config.allow_self_attestation)
+        || !(config.aaguids.is_empty() || config.aaguids.contains(&attestation.aaguid))
+    {
+        return Err(Error::AttestationNotAllowed);
+    }
+
+    Ok((
+        attestation.credential_id,
+        attestation.public_key,
+        attestation.aaguid,
+        data,
+    ))
+}
+
+/// Records the authenticator's signature counter, rejecting counters that did not increase
+///
+/// Authenticators that always report 0 do not implement the counter and are accepted.
+/// A counter that goes backwards or repeats suggests a cloned authenticator; the
+/// rejection rolls back whatever this call records, so `report_clone` records it.
+fn update_sign_count(
+    env: &Env,
+    credential_id: &Bytes,
+    signer: &mut Signer,
+    sign_count: u32,
+) -> Result<(), Error> {
+    if sign_count == 0 && signer.sign_count == 0 {
+        return Ok(());
+    }
+    if sign_count <= signer.sign_count {
+        return Err(Error::SignCountNotIncreased);
+    }
+
+    signer.sign_count = sign_count;
+    env.storage()
+        .instance()
+        .set(&DataKey::Signer(credential_id.clone()), signer);
+    Ok(())
+}
+
+/// Fails with `NotInitialized` until `init` has registered the first signer
+fn require_initialized(env: &Env) -> Result<(), Error> {
+    if !env.storage().instance().has(&DataKey::Signers) {
+        return Err(Error::NotInitialized);
+    }
+    Ok(())
+}
+
+/// Extends the instance (and with it all of the account's data) per the TTL configuration
+fn extend_instance_ttl(env: &Env) {
+    let (threshold, extend_to) = ttl_extension(env);
+    env.storage().instance().extend_ttl(threshold, extend_to);
+}
+
+/// The TTL threshold and extension of the configuration, capped by the maximum TTL
+fn ttl_extension(env: &Env) -> (u32, u32) {
+    let config = PasskeyAccount::get_ttl_config(env.clone());
+    let extend_to = config.extend_to.min(env.storage().max_ttl());
+    (config.threshold.min(extend_to), extend_to)
+}
+
+/// Switches the account to new WASM; the new code runs from the next invocation on
+fn update_wasm(env: &Env, wasm_hash: BytesN<32>) {
+    env.deployer()
+        .update_current_contract_wasm(wasm_hash.clone());
+    events::upgraded(env, &wasm_hash);
+}
+
+/// Passes the authorization to every installed policy contract
+///
+/// Skipped when only policy uninstalls are being authorized.
+fn enforce_policy_contracts(
+    env: &Env,
+    authorizer: &Authorizer,
+    auth_context: &Vec<Context>,
+) -> Result<(), Error> {
+    if is_only_call_to(env, auth_context, "uninstall_policy") {
+        return Ok(());
+    }
+
+    let account = env.current_contract_address();
+    for policy in PasskeyAccount::list_policies(env.clone()).iter() {
+        let client = PolicyContractClient::new(env, &policy);
+        if !matches!(
+            client.try_enforce(&account, authorizer, auth_context),
+            Ok(Ok(()))
+        ) {
+            return Err(Error::PolicyRejected);
+        }
+    }
+
+    Ok(())
+}
+
+/// Hourly amounts of a token moved within the current spending window, oldest first
+///
+/// At most `SPEND_WINDOW_HOURS + 1` buckets are in the window, however many spends
+/// they add up.
+fn recent_spending(env: &Env, token: &Address) -> Vec<SpendBucket> {
+    let spending: Vec<SpendBucket> = env
+        .storage()
+        .persistent()
+        .get(&DataKey::Spending(token.clone()))
+        .unwrap_or(Vec::new(env));
+
+    let expired = spending
+        .iter()
+        .take_while(|bucket| bucket.hour + SPEND_WINDOW_HOURS < current_hour(env))
+        .count() as u32;
+    spending.slice(expired..)
+}
+
+/// Hours since the Unix epoch at the current ledger
+fn current_hour(env: &Env) -> u64 {
+    env.ledger().timestamp() / (60 * 60)
+}
+
+/// Records the token transfers and approvals being authorized against their tokens'
+/// 24-hour limits
+///
+/// `weight` is the total weight of the passkeys that signed (0 for a session key);
+/// reaching a limit's override weight allows exceeding it. Overriding spends are
+/// still recorded.
+fn enforce_spend_limits(env: &Env, auth_context: &Vec<Context>, weight: u64) -> Result<(), Error> {
+    for context in auth_context.iter() {
+        let Some(amount) = policy::spend_amount(env, &context) else {
+            continue;
+        };
+        let Context::Contract(call) = context else {
+            continue;
+        };
+        let Some(spend_limit) = PasskeyAccount::get_spend_limit(env.clone(), call.contract.clone())
+        else {
+            continue;
+        };
+
+        let mut spending = recent_spending(env, &call.contract);
+        let amount = amount.max(0);
+        let spent = spending
+            .iter()
+            .fold(amount, |total, bucket| total.saturating_add(bucket.amount));
+        if spent > spend_limit.limit && weight < u64::from(spend_limit.override_weight) {
+            return Err(Error::DailyLimitExceeded);
+        }
+
+        // Spends within the same hour share a bucket
+        let hour = current_hour(env);
+        match spending.last() {
+            Some(mut bucket) if bucket.hour == hour => {
+                bucket.amount = bucket.amount.saturating_add(amount);
+                spending.set(spending.len() - 1, bucket);
+            }
+            _ => spending.push_back(SpendBucket { hour, amount }),
+        }
+        let key = DataKey::Spending(call.contract.clone());
+        let (threshold, extend_to) = ttl_extension(env);
+        env.storage().persistent().set(&key, &spending);
+        env.storage()
+            .persistent()
+            .extend_ttl(&key, threshold, extend_to);
+    }
+
+    Ok(())
+}
+
+/// Public keys of all registered session keys, including expired ones
+fn session_key_ids(env: &Env) -> Vec<SessionPublicKey> {
+    env.storage()
+        .instance()
+        .get(&DataKey::SessionKeys)
+        .unwrap_or(Vec::new(env))
+}
+
+/// Removes expired session keys and returns the public keys of the others
+fn prune_session_keys(env: &Env) -> Vec<SessionPublicKey> {
+    let mut active = Vec::new(env);
+    for public_key in session_key_ids(env).iter() {
+        match get_session_key(env, &public_key) {
+            Some(session_key) if !session_key.is_expired(env) => active.push_back(public_key),
+            _ => env
+                .storage()
+                .instance()
+                .remove(&DataKey::SessionKey(public_key)),
+        }
+    }
+    env.storage().instance().set(&DataKey::SessionKeys, &active);
+    active
+}
+
+/// Looks up a registered session key, expired or not
+fn get_session_key(env: &Env, public_key: &SessionPublicKey) -> Option<SessionKey> {
+    env.storage()
+        .instance()
+        .get(&DataKey::SessionKey(public_key.clone()))
+}
+
+/// Checks the address is a guardian and that it authorized the call
+fn require_guardian(env: &Env, guardian: &Address) -> Result<RecoveryConfig, Error> {
+    let config = PasskeyAccount::get_recovery_config(env.clone()).ok_or(Error::NotGuardian)?;
+    if !config.guardians.contains(guardian) {
+        return Err(Error::NotGuardian);
+    }
+    guardian.require_auth();
+    Ok(config)
+}
+
+/// Whether the only thing being authorized is calling `fn_name` on this account
+fn is_only_call_to(env: &Env, auth_context: &Vec<Context>, fn_name: &str) -> bool {
+    let fn_name = Symbol::new(env, fn_name);
+    !auth_context.is_empty()
+        && auth_context.iter().all(|context| match context {
+            Context::Contract(call) => {
+                call.contract == env.current_contract_address() && call.fn_name == fn_name
+            }
+            _ => false,
+        })
+}
+
+/// Sums the weights of all registered passkeys
+fn total_weight(env: &Env) -> Result<u64, Error> {
+    let signers = PasskeyAccount::list_signers(env.clone())?;
+    let mut total: u64 = 0;
+    for credential_id in signers.iter() {
+        total += u64::from(PasskeyAccount::get_signer(env.clone(), credential_id)?.weight);
+    }
+    Ok(total)
+}
+
+/// Takes the X || Y coordinates of a raw or uncompressed SEC1 passkey public key
+fn normalize_public_key(env: &Env, public_key: &Bytes) -> Result<BytesN<64>, Error> {
+    let coordinates = public_key::normalize(public_key).map_err(|_| Error::InvalidPublicKey)?;
+    Ok(BytesN::from_array(env, &coordinates))
+}
+
+/// Registers a new passkey under its credential ID
+///
+/// A public key can only be registered once, so that one authenticator cannot count
+/// several times towards the threshold.
+fn store_new_signer(
+    env: &Env,
+    credential_id: Bytes,
+    public_key: BytesN<64>,
+    weight: u32,
+) -> Result<(), Error> {
+    if weight == 0 {
+        return Err(Error::InvalidWeight);
+    }
+
+    let key = DataKey::Signer(credential_id.clone());
+    if env.storage().instance().has(&key) {
+        return Err(Error::SignerAlreadyExists);
+    }
+
+    let mut signers: Vec<Bytes> = env
+        .storage()
+        .instance()
+        .get(&DataKey::Signers)
+        .ok_or(Error::NotInitialized)?;
+    for registered in signers.iter() {
+        if PasskeyAccount::get_signer(env.clone(), registered)?.public_key == public_key {
+            return Err(Error::PublicKeyAlreadyRegistered);
+        }
+    }
+    signers.push_back(credential_id);
+    env.storage().instance().set(&DataKey::Signers, &signers);
+
+    env.storage().instance().set(
+        &key,
+        &Signer {
+            public_key,
+            sign_count: 0,
+            weight,
+        },
+    );
+
+    Ok(())
+}
+
+/// Removes every passkey along with its policies, leaving no signer
+fn clear_signers(env: &Env) -> Result<(), Error> {
+    for credential_id in PasskeyAccount::list_signers(env.clone())?.iter() {
+        env.storage()
+            .instance()
+            .remove(&DataKey::Signer(credential_id.clone()));
+        env.storage()
+            .instance()
+            .remove(&DataKey::SignerPolicy(credential_id.clone()));
+        env.storage()
+            .instance()
+            .remove(&DataKey::BackupPolicy(credential_id.clone()));
+        env.storage()
+            .instance()
+            .remove(&DataKey::CloneReport(credential_id));
+    }
+    env.storage()
+        .instance()
+        .set(&DataKey::Signers, &Vec::<Bytes>::new(env));
+    Ok(())
+}
+
+/// Stores a passkey's backup policy; `Any` is the default and is not stored
+fn store_backup_policy(env: &Env, credential_id: &Bytes, policy: BackupPolicy) {
+    let key = DataKey::BackupPolicy(credential_id.clone());
+    if policy == BackupPolicy::Any {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &policy);
+    }
+    events::backup_policy_set(env, credential_id, policy);
+}
+
+// Disable original passkey tests when building another contract
+#[cfg(all(
+    test,
+    not(any(feature = "factory", feature = "loan", feature = "attestation"))
+))]
+mod test;
+
+#[cfg(all(
+    target_family = "wasm",
+    any(
+        all(feature = "factory", feature = "loan"),
+        all(feature = "factory", feature = "attestation"),
+        all(feature = "loan", feature = "attestation")
+    )
+))]
+compile_error!("enable at most one of the `factory`, `loan` and `attestation` features per wasm");
+
+// ====== Account Factory Contract ======
+// Deploys passkey accounts, compiled as a separate contract with the `factory` feature
+
+#[cfg(feature = "factory")]
+pub mod factory;
+
+// ====== Attestation Verifier Contract ======
+// Verifies `packed` attestation for accounts, compiled as a separate contract with the
+// `attestation` feature
+
+#[cfg(any(test, feature = "attestation"))]
+pub mod attestation_verifier;
 
 // ====== EBAS Credit Scoring Loan Contract ======
 // This is compiled as a separate contract using feature flags
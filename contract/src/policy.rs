@@ -0,0 +1,81 @@
+//! Authorization policies over the calls a signer may authorize
+//!
+//! A policy restricts which contracts and functions a signer may call, and how
+//! much a single token `transfer` or `approve` may move.
+
+use soroban_sdk::{
+    auth::{Context, ContractContext},
+    contracttype, Address, Env, Symbol, TryFromVal, Vec,
+};
+
+/// A contract that may be called
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AllowedCall {
+    /// The contract that may be called
+    pub contract: Address,
+    /// Functions that may be called on it; empty allows every function
+    pub functions: Vec<Symbol>,
+}
+
+/// Rules a signer's authorizations must follow
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Policy {
+    /// Calls the signer may authorize; empty allows any contract except the account itself
+    pub allowed_calls: Vec<AllowedCall>,
+    /// Maximum amount of a single token `transfer` or `approve`, if capped
+    pub max_amount: Option<i128>,
+}
+
+impl AllowedCall {
+    /// Whether the call is to this contract and one of the allowed functions
+    pub fn matches(&self, call: &ContractContext) -> bool {
+        self.contract == call.contract
+            && (self.functions.is_empty() || self.functions.contains(&call.fn_name))
+    }
+}
+
+impl Policy {
+    /// Whether the policy allows authorizing the call
+    ///
+    /// Only contract calls can be allowed; deploying contracts never is.
+    pub fn allows(&self, env: &Env, context: &Context) -> bool {
+        let Context::Contract(call) = context else {
+            return false;
+        };
+
+        let listed = if self.allowed_calls.is_empty() {
+            call.contract != env.current_contract_address()
+        } else {
+            self.allowed_calls
+                .iter()
+                .any(|allowed| allowed.matches(call))
+        };
+
+        listed
+            && match (self.max_amount, spend_amount(env, context)) {
+                (Some(max_amount), Some(amount)) => amount <= max_amount,
+                _ => true,
+            }
+    }
+}
+
+/// Amount of a token `transfer(from, to, amount)` or
+/// `approve(from, spender, amount, expiration_ledger)` call
+///
+/// An amount that cannot be read counts as `i128::MAX`, so caps fail closed.
+pub fn spend_amount(env: &Env, context: &Context) -> Option<i128> {
+    let Context::Contract(call) = context else {
+        return None;
+    };
+    if call.fn_name != Symbol::new(env, "transfer") && call.fn_name != Symbol::new(env, "approve") {
+        return None;
+    }
+
+    let amount = call
+        .args
+        .get(2)
+        .and_then(|amount| i128::try_from_val(env, &amount).ok());
+    Some(amount.unwrap_or(i128::MAX))
+}
@@ -0,0 +1,35 @@
+//! Interface of external policy contracts
+//!
+//! Policy contracts installed on an account are called by `__check_auth` after the
+//! signatures are verified, and reject the authorization by failing. They let new
+//! controls (allow-lists, velocity limits, compliance hooks) be added without
+//! redeploying the account.
+
+use soroban_sdk::{auth::Context, contractclient, contracttype, Address, Bytes, Env, Vec};
+
+use crate::SessionPublicKey;
+
+/// Who signed the authorization being checked
+#[contracttype(export = false)]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum Authorizer {
+    /// Credential IDs of the passkeys that signed
+    Passkeys(Vec<Bytes>),
+    /// The session key that signed
+    SessionKey(SessionPublicKey),
+}
+
+/// Entry point every policy contract implements
+///
+/// Anyone can call a policy directly, so both entry points must call
+/// `account.require_auth()`, which the account satisfies by being the caller. `enforce`
+/// must also fail for accounts that did not `install` the policy, so that no one can
+/// drive a stateful policy (e.g. velocity limits) on an account's behalf.
+#[contractclient(name = "PolicyContractClient")]
+pub trait PolicyContract {
+    /// Records `account` as having installed the policy, failing to refuse it
+    fn install(env: Env, account: Address);
+
+    /// Fails to reject the authorization of `auth_contexts` by `authorizer`
+    fn enforce(env: Env, account: Address, authorizer: Authorizer, auth_contexts: Vec<Context>);
+}
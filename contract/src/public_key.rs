@@ -0,0 +1,241 @@
+//! Parsing and validation of passkey public keys
+//!
+//! The account takes a key as its raw `X || Y` coordinates (64 bytes) or its
+//! uncompressed SEC1 point (65 bytes), and does not check it is on the curve: the
+//! field arithmetic would not fit in its wasm, and the host rejects such a key when it
+//! verifies a signature, so the passkey could never sign.
+//!
+//! The factory and the attestation verifier also accept the compressed SEC1 point
+//! (33 bytes) and the COSE_Key found in the attestation object's credential data, and
+//! check every form is a point on the P-256 curve.
+
+#[cfg(any(test, feature = "factory", feature = "attestation"))]
+use p256::{
+    elliptic_curve::sec1::{FromEncodedPoint, ToEncodedPoint},
+    AffinePoint, EncodedPoint,
+};
+use soroban_sdk::Bytes;
+
+#[cfg(any(test, feature = "factory", feature = "attestation"))]
+use crate::cbor::{Decoder, Malformed};
+
+/// Maximum accepted size of an encoded public key
+#[cfg(feature = "factory")]
+const MAX_KEY_LEN: usize = 128;
+
+/// The key is not a P-256 point in a supported encoding
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub struct InvalidKey;
+
+#[cfg(any(test, feature = "factory", feature = "attestation"))]
+impl From<Malformed> for InvalidKey {
+    fn from(_: Malformed) -> Self {
+        InvalidKey
+    }
+}
+
+/// Takes the `X || Y` coordinates of a raw or uncompressed SEC1 key, without checking
+/// they are on the curve
+pub fn normalize(public_key: &Bytes) -> Result<[u8; 64], InvalidKey> {
+    let start = match (public_key.len(), public_key.first()) {
+        (64, _) => 0,
+        (65, Some(0x04)) => 1,
+        _ => return Err(InvalidKey),
+    };
+    let mut coordinates = [0u8; 64];
+    public_key.slice(start..).copy_into_slice(&mut coordinates);
+    Ok(coordinates)
+}
+
+/// Parses a public key in any supported encoding into its `X || Y` coordinates,
+/// checking it is on the curve
+#[cfg(feature = "factory")]
+pub fn decode(public_key: &Bytes) -> Result<[u8; 64], InvalidKey> {
+    let len = public_key.len() as usize;
+    if len > MAX_KEY_LEN {
+        return Err(InvalidKey);
+    }
+
+    let mut buffer = [0u8; MAX_KEY_LEN];
+    let encoded = &mut buffer[..len];
+    public_key.copy_into_slice(encoded);
+    parse(encoded)
+}
+
+/// Parses a public key in any supported encoding from a byte slice, checking it is on
+/// the curve
+#[cfg(any(test, feature = "factory", feature = "attestation"))]
+pub fn parse(encoded: &[u8]) -> Result<[u8; 64], InvalidKey> {
+    match (encoded.len(), encoded.first()) {
+        (33, Some(0x02 | 0x03)) | (65, Some(0x04)) => validate(encoded),
+        (64, _) => validate(&sec1_uncompressed(encoded)),
+        // A CBOR map
+        (_, Some(0xa0..=0xbb)) => validate(&sec1_uncompressed(&parse_cose(encoded)?)),
+        _ => Err(InvalidKey),
+    }
+}
+
+/// Extracts the coordinates of an EC2 P-256 COSE_Key
+#[cfg(any(test, feature = "factory", feature = "attestation"))]
+fn parse_cose(encoded: &[u8]) -> Result<[u8; 64], InvalidKey> {
+    // COSE_Key labels and values for an ES256 key (RFC 9053)
+    const COSE_KTY: i64 = 1;
+    const COSE_ALG: i64 = 3;
+    const COSE_EC2_CRV: i64 = -1;
+    const COSE_EC2_X: i64 = -2;
+    const COSE_EC2_Y: i64 = -3;
+    const COSE_KTY_EC2: i64 = 2;
+    const COSE_ALG_ES256: i64 = -7;
+    const COSE_CRV_P256: i64 = 1;
+
+    let mut decoder = Decoder::new(encoded);
+    let (mut kty, mut alg, mut crv) = (None, None, None);
+    let (mut x, mut y) = (None, None);
+
+    for _ in 0..decoder.map()? {
+        let label = decoder.int()?;
+        let slot = match label {
+            COSE_KTY => &mut kty,
+            COSE_ALG => &mut alg,
+            COSE_EC2_CRV => &mut crv,
+            COSE_EC2_X | COSE_EC2_Y => {
+                let coordinate = if label == COSE_EC2_X { &mut x } else { &mut y };
+                if coordinate.replace(decoder.bytes()?).is_some() {
+                    return Err(InvalidKey);
+                }
+                continue;
+            }
+            _ => {
+                decoder.skip()?;
+                continue;
+            }
+        };
+        if slot.replace(decoder.int()?).is_some() {
+            return Err(InvalidKey);
+        }
+    }
+
+    if !decoder.is_empty()
+        || kty != Some(COSE_KTY_EC2)
+        || crv != Some(COSE_CRV_P256)
+        || alg.is_some_and(|alg| alg != COSE_ALG_ES256)
+    {
+        return Err(InvalidKey);
+    }
+
+    let mut coordinates = [0u8; 64];
+    match (x, y) {
+        (Some(x), Some(y)) if x.len() == 32 && y.len() == 32 => {
+            coordinates[..32].copy_from_slice(x);
+            coordinates[32..].copy_from_slice(y);
+            Ok(coordinates)
+        }
+        _ => Err(InvalidKey),
+    }
+}
+
+/// Checks a SEC1 point is on the curve and returns its `X || Y` coordinates
+#[cfg(any(test, feature = "factory", feature = "attestation"))]
+fn validate(sec1: &[u8]) -> Result<[u8; 64], InvalidKey> {
+    let point = EncodedPoint::from_bytes(sec1).map_err(|_| InvalidKey)?;
+    let point: Option<AffinePoint> = AffinePoint::from_encoded_point(&point).into();
+    let point = point.ok_or(InvalidKey)?.to_encoded_point(false);
+
+    let mut coordinates = [0u8; 64];
+    coordinates.copy_from_slice(&point.as_bytes()[1..]);
+    Ok(coordinates)
+}
+
+#[cfg(any(test, feature = "factory", feature = "attestation"))]
+fn sec1_uncompressed(coordinates: &[u8]) -> [u8; 65] {
+    let mut sec1 = [0u8; 65];
+    sec1[0] = 0x04;
+    sec1[1..].copy_from_slice(coordinates);
+    sec1
+}
+
+#[cfg(test)]
+mod public_key_test {
+    use super::*;
+
+    /// The P-256 generator point
+    const G_X: [u8; 32] = [
+        0x6b, 0x17, 0xd1, 0xf2, 0xe1, 0x2c, 0x42, 0x47, 0xf8, 0xbc, 0xe6, 0xe5, 0x63, 0xa4, 0x40,
+        0xf2, 0x77, 0x03, 0x7d, 0x81, 0x2d, 0xeb, 0x33, 0xa0, 0xf4, 0xa1, 0x39, 0x45, 0xd8, 0x98,
+        0xc2, 0x96,
+    ];
+    const G_Y: [u8; 32] = [
+        0x4f, 0xe3, 0x42, 0xe2, 0xfe, 0x1a, 0x7f, 0x9b, 0x8e, 0xe7, 0xeb, 0x4a, 0x7c, 0x0f, 0x9e,
+        0x16, 0x2b, 0xce, 0x33, 0x57, 0x6b, 0x31, 0x5e, 0xce, 0xcb, 0xb6, 0x40, 0x68, 0x37, 0xbf,
+        0x51, 0xf5,
+    ];
+
+    fn coordinates() -> [u8; 64] {
+        let mut coordinates = [0u8; 64];
+        coordinates[..32].copy_from_slice(&G_X);
+        coordinates[32..].copy_from_slice(&G_Y);
+        coordinates
+    }
+
+    /// COSE_Key `{1: 2, 3: -7, -1: 1, -2: x, -3: y}`
+    fn cose_key(x: &[u8; 32], y: &[u8; 32]) -> [u8; 77] {
+        let mut key = [0u8; 77];
+        key[..10].copy_from_slice(&[0xa5, 0x01, 0x02, 0x03, 0x26, 0x20, 0x01, 0x21, 0x58, 0x20]);
+        key[10..42].copy_from_slice(x);
+        key[42..45].copy_from_slice(&[0x22, 0x58, 0x20]);
+        key[45..].copy_from_slice(y);
+        key
+    }
+
+    #[test]
+    fn test_encodings_normalize_to_coordinates() {
+        // y is odd
+        let mut compressed = [0x03; 33];
+        compressed[1..].copy_from_slice(&G_X);
+
+        assert_eq!(parse(&compressed), Ok(coordinates()));
+        assert_eq!(parse(&sec1_uncompressed(&coordinates())), Ok(coordinates()));
+        assert_eq!(parse(&coordinates()), Ok(coordinates()));
+        assert_eq!(parse(&cose_key(&G_X, &G_Y)), Ok(coordinates()));
+    }
+
+    #[test]
+    fn test_rejects_points_off_the_curve() {
+        let mut off_curve = coordinates();
+        off_curve[63] ^= 1;
+        assert_eq!(parse(&off_curve), Err(InvalidKey));
+        assert_eq!(parse(&sec1_uncompressed(&off_curve)), Err(InvalidKey));
+        assert_eq!(parse(&[0u8; 64]), Err(InvalidKey));
+
+        // x beyond the field modulus
+        let mut compressed = [0xff; 33];
+        compressed[0] = 0x02;
+        assert_eq!(parse(&compressed), Err(InvalidKey));
+
+        let mut y = G_Y;
+        y[31] ^= 1;
+        assert_eq!(parse(&cose_key(&G_X, &y)), Err(InvalidKey));
+    }
+
+    #[test]
+    fn test_rejects_other_encodings() {
+        assert_eq!(parse(&[]), Err(InvalidKey));
+        assert_eq!(parse(&[0x04; 33]), Err(InvalidKey));
+        assert_eq!(parse(&[0x02; 65]), Err(InvalidKey));
+
+        // Another curve or algorithm
+        let mut key = cose_key(&G_X, &G_Y);
+        key[6] = 0x02;
+        assert_eq!(parse(&key), Err(InvalidKey));
+        let mut key = cose_key(&G_X, &G_Y);
+        key[4] = 0x27;
+        assert_eq!(parse(&key), Err(InvalidKey));
+
+        // Truncated or followed by trailing bytes
+        let key = cose_key(&G_X, &G_Y);
+        assert_eq!(parse(&key[..76]), Err(InvalidKey));
+        let mut trailing = [0u8; 78];
+        trailing[..77].copy_from_slice(&key);
+        assert_eq!(parse(&trailing), Err(InvalidKey));
+    }
+}
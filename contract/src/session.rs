@@ -0,0 +1,128 @@
+//! Time-limited session keys
+//!
+//! A session key is an ed25519 or secp256r1 key held by a dApp that may authorize
+//! a restricted set of contract calls until an expiry ledger, without prompting
+//! for the passkey. Session keys sign the signature payload directly (no WebAuthn).
+
+use soroban_sdk::{auth::Context, contracttype, crypto::Hash, Address, BytesN, Env, Map, Vec};
+
+use crate::policy::{spend_amount, AllowedCall};
+use crate::Error;
+
+/// Public key of a session key
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SessionPublicKey {
+    /// 32-byte ed25519 public key
+    Ed25519(BytesN<32>),
+    /// 64-byte uncompressed secp256r1 public key (X || Y)
+    Secp256r1(BytesN<64>),
+}
+
+/// A registered session key and what it may do
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct SessionKey {
+    pub public_key: SessionPublicKey,
+    /// Last ledger sequence the key is valid for
+    pub expiration_ledger: u32,
+    /// Calls the key may authorize
+    pub allowed_calls: Vec<AllowedCall>,
+    /// Maximum total amount of each capped token the key may `transfer` or `approve`
+    pub spend_limits: Map<Address, i128>,
+    /// Amount of each capped token transferred or approved with the key so far
+    pub spent: Map<Address, i128>,
+}
+
+/// A signature by a session key over the signature payload
+#[contracttype]
+#[derive(Clone)]
+pub struct SessionSignature {
+    pub public_key: SessionPublicKey,
+    /// ed25519 signature, or secp256r1 signature (r || s, low-S normalized)
+    pub signature: BytesN<64>,
+}
+
+impl SessionKey {
+    /// Whether the key can no longer be used
+    pub fn is_expired(&self, env: &Env) -> bool {
+        env.ledger().sequence() > self.expiration_ledger
+    }
+
+    /// Whether every call being authorized is one the key may make
+    pub fn allows(&self, auth_context: &Vec<Context>) -> bool {
+        auth_context.iter().all(|context| match context {
+            Context::Contract(call) => self
+                .allowed_calls
+                .iter()
+                .any(|allowed| allowed.matches(&call)),
+            _ => false,
+        })
+    }
+
+    /// Adds the amounts being transferred or approved to what the key spent of each
+    /// capped token so far
+    ///
+    /// Only capped tokens are counted, so an unreadable amount (counted as `i128::MAX`)
+    /// only fails against a cap. Negative amounts count as 0.
+    ///
+    /// # Errors
+    /// * `SpendLimitExceeded` - If the total would exceed the token's spend limit
+    pub fn spend(&mut self, env: &Env, auth_context: &Vec<Context>) -> Result<(), Error> {
+        for context in auth_context.iter() {
+            let Some(amount) = spend_amount(env, &context) else {
+                continue;
+            };
+            let Context::Contract(call) = context else {
+                continue;
+            };
+            let Some(limit) = self.spend_limits.get(call.contract.clone()) else {
+                continue;
+            };
+
+            let spent = self
+                .spent
+                .get(call.contract.clone())
+                .unwrap_or(0)
+                .saturating_add(amount.max(0));
+            if spent > limit {
+                return Err(Error::SpendLimitExceeded);
+            }
+            self.spent.set(call.contract, spent);
+        }
+
+        Ok(())
+    }
+}
+
+/// Verifies a session key signature
+///
+/// ed25519 keys sign `payload`; secp256r1 keys sign `digest`, which must be the
+/// payload itself or, for a payload the host did not hash, its SHA-256.
+///
+/// # Errors
+/// * `InvalidSignature` - If a secp256r1 signature is out of range or not low-S
+pub fn verify(
+    env: &Env,
+    payload: &BytesN<32>,
+    digest: &Hash<32>,
+    signature: &SessionSignature,
+) -> Result<(), Error> {
+    match &signature.public_key {
+        SessionPublicKey::Ed25519(public_key) => {
+            env.crypto()
+                .ed25519_verify(public_key, &payload.into(), &signature.signature)
+        }
+        SessionPublicKey::Secp256r1(public_key) => {
+            if !crate::is_valid_signature_encoding(&signature.signature.to_array()) {
+                return Err(Error::InvalidSignature);
+            }
+            env.crypto().secp256r1_verify(
+                &crate::sec1_uncompressed(env, public_key),
+                digest,
+                &signature.signature,
+            )
+        }
+    }
+    Ok(())
+}
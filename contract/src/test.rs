@@ -2,114 +2,3371 @@
 extern crate std;
 
 use super::*;
-use soroban_sdk::{testutils::BytesN as _, Env};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
+use p256::ecdsa::{signature::hazmat::PrehashSigner, SigningKey};
+use soroban_sdk::{
+    contract, contractimpl,
+    testutils::{storage::Instance as _, Address as _, BytesN as _, Events as _, Ledger as _},
+    Address, Env, IntoVal,
+};
+use std::format;
+
+/// Deterministic P-256 key used to sign test payloads
+fn signing_key(seed: u8) -> SigningKey {
+    SigningKey::from_bytes(&[seed; 32].into()).unwrap()
+}
+
+/// Credential ID the test authenticator reports for a signing key
+fn credential_id(env: &Env, key: &SigningKey) -> Bytes {
+    let point = key.verifying_key().to_encoded_point(false);
+    Bytes::from_slice(env, &point.as_bytes()[1..17])
+}
+
+/// Returns the raw 64-byte `X || Y` public key of a signing key
+fn public_key(env: &Env, key: &SigningKey) -> BytesN<64> {
+    let point = key.verifying_key().to_encoded_point(false);
+    BytesN::from_array(env, point.as_bytes()[1..].try_into().unwrap())
+}
+
+/// Returns the 65-byte uncompressed SEC1 public key of a signing key
+fn sec1_public_key(env: &Env, key: &SigningKey) -> Bytes {
+    let point = key.verifying_key().to_encoded_point(false);
+    Bytes::from_slice(env, point.as_bytes())
+}
+
+/// Authenticator data for `rp_id` with the given flags and counter
+fn authenticator_data_with(env: &Env, rp_id: &[u8], flags: u8, counter: u32) -> Bytes {
+    let mut data = Bytes::from_array(
+        env,
+        &env.crypto()
+            .sha256(&Bytes::from_slice(env, rp_id))
+            .to_array(),
+    );
+    data.push_back(flags);
+    data.extend_from_array(&counter.to_be_bytes());
+    data
+}
+
+/// Authenticator data for `localhost` with the UP and UV flags set and a zero counter
+fn authenticator_data(env: &Env) -> Bytes {
+    authenticator_data_with(env, b"localhost", 0x05, 0)
+}
+
+/// clientDataJSON as a browser would produce it for the given challenge
+fn client_data_json(challenge: &BytesN<32>) -> std::string::String {
+    format!(
+        r#"{{"type":"webauthn.get","challenge":"{}","origin":"https://localhost","crossOrigin":false}}"#,
+        URL_SAFE_NO_PAD.encode(challenge.to_array())
+    )
+}
+
+/// Signs `authenticatorData || sha256(clientDataJSON)` the way an authenticator does,
+/// returning a low-S normalized assertion
+fn sign_assertion(
+    env: &Env,
+    key: &SigningKey,
+    authenticator_data: Bytes,
+    client_data_json: &[u8],
+) -> Signature {
+    let client_data_json = Bytes::from_slice(env, client_data_json);
+    let mut message = authenticator_data.clone();
+    message.extend_from_array(&env.crypto().sha256(&client_data_json).to_array());
+    let digest = env.crypto().sha256(&message).to_array();
+
+    let signature: p256::ecdsa::Signature = key.sign_prehash(&digest).unwrap();
+    let signature = signature.normalize_s().unwrap_or(signature);
+
+    Signature {
+        credential_id: credential_id(env, key),
+        authenticator_data,
+        client_data_json,
+        signature: BytesN::from_array(env, &signature.to_bytes().into()),
+    }
+}
+
+/// Produces a WebAuthn assertion over a Soroban signature payload
+fn sign(env: &Env, key: &SigningKey, payload: &BytesN<32>) -> Signature {
+    sign_assertion(
+        env,
+        key,
+        authenticator_data(env),
+        client_data_json(payload).as_bytes(),
+    )
+}
+
+fn check_auth(
+    env: &Env,
+    contract_id: &Address,
+    payload: &BytesN<32>,
+    signature: Signature,
+) -> Result<(), Result<Error, soroban_sdk::InvokeError>> {
+    check_auth_signatures(env, contract_id, payload, soroban_sdk::vec![env, signature])
+}
+
+fn check_auth_signatures(
+    env: &Env,
+    contract_id: &Address,
+    payload: &BytesN<32>,
+    signatures: Vec<Signature>,
+) -> Result<(), Result<Error, soroban_sdk::InvokeError>> {
+    let authorization = Authorization::Passkeys(signatures);
+    check_auth_for(env, contract_id, payload, authorization, Vec::new(env))
+}
+
+/// Runs `__check_auth` for the calls in `auth_context`
+fn check_auth_for(
+    env: &Env,
+    contract_id: &Address,
+    payload: &BytesN<32>,
+    authorization: Authorization,
+    auth_context: Vec<Context>,
+) -> Result<(), Result<Error, soroban_sdk::InvokeError>> {
+    env.try_invoke_contract_check_auth::<Error>(
+        contract_id,
+        payload,
+        authorization.into_val(env),
+        &auth_context,
+    )
+}
+
+/// The origin test assertions are produced for
+fn allowed_origins(env: &Env) -> Vec<Bytes> {
+    soroban_sdk::vec![env, Bytes::from_slice(env, b"https://localhost")]
+}
+
+/// The relying party ID test passkeys are registered for
+fn rp_id(env: &Env) -> Bytes {
+    Bytes::from_slice(env, b"localhost")
+}
+
+fn setup(env: &Env, key: &SigningKey) -> Address {
+    let contract_id = env.register_contract(None, PasskeyAccount);
+    let client = PasskeyAccountClient::new(env, &contract_id);
+    client.init(
+        &sec1_public_key(env, key),
+        &credential_id(env, key),
+        &allowed_origins(env),
+        &rp_id(env),
+        &true,
+    );
+    contract_id
+}
 
 #[test]
 fn test_init_success() {
     let env = Env::default();
-    let contract_id = env.register(PasskeyAccount, ());
+    let contract_id = env.register_contract(None, PasskeyAccount);
     let client = PasskeyAccountClient::new(&env, &contract_id);
 
-    // Generate a random 64-byte public key (simulating a secp256r1 public key)
-    let public_key = BytesN::random(&env);
+    let key = signing_key(7);
     let credential_id = Bytes::from_slice(&env, b"test-credential-id");
 
     // Initialize the contract
-    let result = client.init(&public_key, &Some(credential_id.clone()));
-    assert!(result.is_ok());
-
-    // Verify the owner was set
-    let owner = client.get_owner();
-    assert!(owner.is_ok());
-    assert_eq!(owner.unwrap(), public_key);
+    client.init(
+        &sec1_public_key(&env, &key),
+        &credential_id,
+        &allowed_origins(&env),
+        &rp_id(&env),
+        &true,
+    );
 
-    // Verify credential ID was set
-    let stored_cred_id = client.get_credential_id();
-    assert_eq!(stored_cred_id, Some(credential_id));
+    // Verify the passkey was registered as the only signer
+    assert_eq!(
+        client.list_signers(),
+        soroban_sdk::vec![&env, credential_id.clone()]
+    );
+    assert_eq!(
+        client.get_signer(&credential_id),
+        Signer {
+            public_key: public_key(&env, &key),
+            sign_count: 0,
+            weight: 1
+        }
+    );
 }
 
 #[test]
 fn test_init_already_initialized() {
     let env = Env::default();
-    let contract_id = env.register(PasskeyAccount, ());
+    let contract_id = env.register_contract(None, PasskeyAccount);
     let client = PasskeyAccountClient::new(&env, &contract_id);
 
-    let public_key = BytesN::random(&env);
+    let public_key = sec1_public_key(&env, &signing_key(7));
+    let credential_id = Bytes::from_slice(&env, b"test-credential-id");
 
     // Initialize once
-    client.init(&public_key, &None).unwrap();
+    client.init(
+        &public_key,
+        &credential_id,
+        &allowed_origins(&env),
+        &rp_id(&env),
+        &true,
+    );
 
     // Try to initialize again
-    let result = client.init(&public_key, &None);
+    let result = client.try_init(
+        &public_key,
+        &credential_id,
+        &allowed_origins(&env),
+        &rp_id(&env),
+        &true,
+    );
     assert_eq!(result, Err(Ok(Error::AlreadyInitialized)));
 }
 
 #[test]
-fn test_get_owner_not_initialized() {
+fn test_list_signers_not_initialized() {
     let env = Env::default();
-    let contract_id = env.register(PasskeyAccount, ());
+    let contract_id = env.register_contract(None, PasskeyAccount);
     let client = PasskeyAccountClient::new(&env, &contract_id);
 
-    // Try to get owner before initialization
-    let result = client.get_owner();
+    // Try to list signers before initialization
+    let result = client.try_list_signers();
     assert_eq!(result, Err(Ok(Error::NotInitialized)));
 }
 
 #[test]
-fn test_get_credential_id_none() {
+fn test_get_signer_unknown() {
+    let env = Env::default();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+
+    let result = client.try_get_signer(&Bytes::from_slice(&env, b"unknown"));
+    assert_eq!(result, Err(Ok(Error::SignerNotFound)));
+}
+
+#[test]
+fn test_multiple_keys() {
     let env = Env::default();
-    let contract_id = env.register(PasskeyAccount, ());
+    env.mock_all_auths();
+    let laptop = signing_key(7);
+    let phone = signing_key(8);
+    let contract_id = setup(&env, &laptop);
     let client = PasskeyAccountClient::new(&env, &contract_id);
 
-    let public_key = BytesN::random(&env);
+    // Register the phone as a second passkey
+    client.add_signer(
+        &credential_id(&env, &phone),
+        &sec1_public_key(&env, &phone),
+        &1,
+    );
+    assert_eq!(
+        env.auths()[0].0,
+        contract_id,
+        "adding a signer requires the account's own auth"
+    );
+    assert_eq!(
+        client.list_signers(),
+        soroban_sdk::vec![
+            &env,
+            credential_id(&env, &laptop),
+            credential_id(&env, &phone)
+        ]
+    );
+
+    // Either device can sign
+    for key in [&laptop, &phone] {
+        let payload = BytesN::random(&env);
+        let signature = sign(&env, key, &payload);
+        assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+    }
 
-    // Initialize without credential ID
-    client.init(&public_key, &None).unwrap();
+    // The same credential cannot be registered twice
+    let result = client.try_add_signer(
+        &credential_id(&env, &phone),
+        &sec1_public_key(&env, &phone),
+        &1,
+    );
+    assert_eq!(result, Err(Ok(Error::SignerAlreadyExists)));
+}
+
+/// COSE_Key of a signing key, as found in the attestation object
+fn cose_public_key(env: &Env, key: &SigningKey) -> Bytes {
+    Bytes::from_slice(env, &cose_key(key))
+}
 
-    // Verify credential ID is None
-    let cred_id = client.get_credential_id();
-    assert_eq!(cred_id, None);
+fn cose_key(key: &SigningKey) -> std::vec::Vec<u8> {
+    let point = key.verifying_key().to_encoded_point(false);
+    // {1: 2 (EC2), 3: -7 (ES256), -1: 1 (P-256), -2: x, -3: y}
+    let mut cose = std::vec![0xa5, 0x01, 0x02, 0x03, 0x26, 0x20, 0x01, 0x21, 0x58, 0x20];
+    cose.extend_from_slice(point.x().unwrap());
+    cose.extend_from_slice(&[0x22, 0x58, 0x20]);
+    cose.extend_from_slice(point.y().unwrap());
+    cose
 }
 
 #[test]
-fn test_multiple_keys() {
+fn test_public_key_encodings() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+
+    // Raw and uncompressed SEC1 keys are stored as X || Y, and the passkeys can sign
+    let encodings = [
+        (
+            signing_key(8),
+            Bytes::from(public_key(&env, &signing_key(8))),
+        ),
+        (signing_key(9), sec1_public_key(&env, &signing_key(9))),
+    ];
+    for (key, encoded) in encodings.iter() {
+        client.add_signer(&credential_id(&env, key), encoded, &1);
+        let signer = client.get_signer(&credential_id(&env, key));
+        assert_eq!(signer.public_key, public_key(&env, key));
+
+        let payload = BytesN::random(&env);
+        let signature = sign(&env, key, &payload);
+        assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+    }
+
+    // Compressed and COSE keys are left to the factory, other encodings are rejected
+    let other = signing_key(10);
+    let compressed = other.verifying_key().to_encoded_point(true);
+    let invalid = [
+        Bytes::from_slice(&env, compressed.as_bytes()),
+        cose_public_key(&env, &other),
+        Bytes::from_slice(&env, &[0x05; 65]),
+        Bytes::from(public_key(&env, &other)).slice(..63),
+    ];
+    for encoded in invalid.iter() {
+        assert_eq!(
+            client.try_add_signer(&credential_id(&env, &other), encoded, &1),
+            Err(Ok(Error::InvalidPublicKey))
+        );
+    }
+
+    // A point off the curve is stored, but the host rejects its signatures
+    let mut off_curve = public_key(&env, &other).to_array();
+    off_curve[63] ^= 1;
+    client.add_signer(
+        &credential_id(&env, &other),
+        &Bytes::from_array(&env, &off_curve),
+        &1,
+    );
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &other, &payload);
+    assert!(matches!(
+        check_auth(&env, &contract_id, &payload, signature),
+        Err(Err(_))
+    ));
+}
+
+#[test]
+fn test_remove_signer() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let laptop = signing_key(7);
+    let phone = signing_key(8);
+    let contract_id = setup(&env, &laptop);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    client.add_signer(
+        &credential_id(&env, &phone),
+        &sec1_public_key(&env, &phone),
+        &1,
+    );
+
+    // A lost phone is removed and can no longer sign
+    client.remove_signer(&credential_id(&env, &phone));
+    assert_eq!(
+        client.list_signers(),
+        soroban_sdk::vec![&env, credential_id(&env, &laptop)]
+    );
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &phone, &payload);
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::SignerNotFound)));
+
+    // Unknown credentials and the last signer cannot be removed
+    let result = client.try_remove_signer(&credential_id(&env, &phone));
+    assert_eq!(result, Err(Ok(Error::SignerNotFound)));
+    let result = client.try_remove_signer(&credential_id(&env, &laptop));
+    assert_eq!(result, Err(Ok(Error::LastSigner)));
+}
+
+#[test]
+fn test_get_owner() {
+    let env = Env::default();
+    let contract_id = env.register_contract(None, PasskeyAccount);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    assert_eq!(client.try_get_owner(), Err(Ok(Error::NotInitialized)));
+    assert_eq!(client.get_credential_id(), None);
+
+    // The first passkey stands in for the single owner of the original contract
+    let laptop = signing_key(7);
+    let contract_id = setup(&env, &laptop);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    env.mock_all_auths();
+    let phone = signing_key(8);
+    client.add_signer(
+        &credential_id(&env, &phone),
+        &sec1_public_key(&env, &phone),
+        &1,
+    );
+    assert_eq!(client.get_owner(), public_key(&env, &laptop));
+    assert_eq!(
+        client.get_credential_id(),
+        Some(credential_id(&env, &laptop))
+    );
+}
+
+#[test]
+fn test_update_owner() {
+    let env = Env::default();
+    let keys = [signing_key(7), signing_key(8), signing_key(9)];
+    let contract_id = setup_two_of_three(&env, &keys);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    client.set_backup_policy(&credential_id(&env, &keys[1]), &BackupPolicy::DeviceBound);
+
+    // The new key replaces every passkey, under the first one's credential ID
+    let new_key = signing_key(10);
+    client.update_owner(&public_key(&env, &new_key), &None);
+    let first = credential_id(&env, &keys[0]);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "owner_updated"), first.clone()),
+        public_key(&env, &new_key),
+    );
+    assert_eq!(
+        env.auths()[0].0,
+        contract_id,
+        "updating the owner requires the account's own auth"
+    );
+    assert_eq!(
+        client.list_signers(),
+        soroban_sdk::vec![&env, first.clone()]
+    );
+    let signer = client.get_signer(&first);
+    assert_eq!(signer.public_key, public_key(&env, &new_key));
+    assert_eq!(signer.weight, 1);
+    assert_eq!(client.get_threshold(), 1);
+    assert_eq!(client.get_owner(), public_key(&env, &new_key));
+    let result = client.try_get_signer(&credential_id(&env, &keys[1]));
+    assert_eq!(result, Err(Ok(Error::SignerNotFound)));
+    assert_eq!(
+        client.get_backup_policy(&credential_id(&env, &keys[1])),
+        BackupPolicy::Any
+    );
+
+    // ...or under the credential ID given
+    let phone = signing_key(11);
+    client.update_owner(
+        &public_key(&env, &phone),
+        &Some(credential_id(&env, &phone)),
+    );
+    assert_eq!(
+        client.get_credential_id(),
+        Some(credential_id(&env, &phone))
+    );
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &phone, &payload);
+    assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+}
+
+/// A treasury account where 2 of 3 employee passkeys must sign
+fn setup_two_of_three(env: &Env, keys: &[SigningKey; 3]) -> Address {
+    env.mock_all_auths();
+    let contract_id = setup(env, &keys[0]);
+    let client = PasskeyAccountClient::new(env, &contract_id);
+    for key in &keys[1..] {
+        client.add_signer(&credential_id(env, key), &sec1_public_key(env, key), &1);
+    }
+    client.set_threshold(&2);
+    contract_id
+}
+
+#[test]
+fn test_threshold() {
+    let env = Env::default();
+    let keys = [signing_key(7), signing_key(8), signing_key(9)];
+    let contract_id = setup_two_of_three(&env, &keys);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    assert_eq!(client.get_threshold(), 2);
+
+    // One employee alone is not enough
+    let payload = BytesN::random(&env);
+    let result = check_auth(&env, &contract_id, &payload, sign(&env, &keys[0], &payload));
+    assert_eq!(result, Err(Ok(Error::ThresholdNotMet)));
+
+    // Any two of them are
+    let payload = BytesN::random(&env);
+    let signatures = soroban_sdk::vec![
+        &env,
+        sign(&env, &keys[2], &payload),
+        sign(&env, &keys[0], &payload)
+    ];
+    assert_eq!(
+        check_auth_signatures(&env, &contract_id, &payload, signatures),
+        Ok(())
+    );
+
+    // No signatures at all never meet the threshold
+    let result = check_auth_signatures(&env, &contract_id, &payload, Vec::new(&env));
+    assert_eq!(result, Err(Ok(Error::ThresholdNotMet)));
+}
+
+#[test]
+fn test_threshold_counts_each_signer_once() {
+    let env = Env::default();
+    let keys = [signing_key(7), signing_key(8), signing_key(9)];
+    let contract_id = setup_two_of_three(&env, &keys);
+
+    // Two assertions from the same passkey (different counters) still count once
+    let payload = BytesN::random(&env);
+    let cdj = client_data_json(&payload);
+    let signatures = soroban_sdk::vec![
+        &env,
+        sign_assertion(
+            &env,
+            &keys[1],
+            authenticator_data_with(&env, b"localhost", 0x05, 1),
+            cdj.as_bytes()
+        ),
+        sign_assertion(
+            &env,
+            &keys[1],
+            authenticator_data_with(&env, b"localhost", 0x05, 2),
+            cdj.as_bytes()
+        )
+    ];
+    let result = check_auth_signatures(&env, &contract_id, &payload, signatures);
+    assert_eq!(result, Err(Ok(Error::DuplicateSignature)));
+}
+
+#[test]
+fn test_public_key_registered_once() {
+    let env = Env::default();
+    let keys = [signing_key(7), signing_key(8), signing_key(9)];
+    let contract_id = setup_two_of_three(&env, &keys);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+
+    // A passkey cannot be registered again under another credential ID
+    let other_id = Bytes::from_slice(&env, b"other credential");
+    let result = client.try_add_signer(&other_id, &sec1_public_key(&env, &keys[1]), &1);
+    assert_eq!(result, Err(Ok(Error::PublicKeyAlreadyRegistered)));
+
+    // A key already registered twice still counts once
+    env.as_contract(&contract_id, || {
+        store_new_signer_unchecked(&env, &other_id, public_key(&env, &keys[1]));
+    });
+    let payload = BytesN::random(&env);
+    let mut second = sign(&env, &keys[1], &payload);
+    second.credential_id = other_id;
+    let signatures = soroban_sdk::vec![&env, sign(&env, &keys[1], &payload), second];
+    let result = check_auth_signatures(&env, &contract_id, &payload, signatures);
+    assert_eq!(result, Err(Ok(Error::DuplicateSignature)));
+}
+
+/// Registers a passkey bypassing the checks of `add_signer`, as older code did
+fn store_new_signer_unchecked(env: &Env, credential_id: &Bytes, public_key: BytesN<64>) {
+    let mut signers = PasskeyAccount::list_signers(env.clone()).unwrap();
+    signers.push_back(credential_id.clone());
+    env.storage().instance().set(&DataKey::Signers, &signers);
+    env.storage().instance().set(
+        &DataKey::Signer(credential_id.clone()),
+        &Signer {
+            public_key,
+            sign_count: 0,
+            weight: 1,
+        },
+    );
+}
+
+#[test]
+fn test_weighted_signers() {
     let env = Env::default();
-    let contract_id = env.register(PasskeyAccount, ());
+    env.mock_all_auths();
+    let [clerk, manager, owner] = [signing_key(7), signing_key(8), signing_key(9)];
+    let contract_id = setup(&env, &clerk);
     let client = PasskeyAccountClient::new(&env, &contract_id);
 
-    // Test with different public keys
-    let public_key1 = BytesN::random(&env);
-    let public_key2 = BytesN::random(&env);
+    // Weights 1, 2 and 3 with a threshold of 3
+    client.add_signer(
+        &credential_id(&env, &manager),
+        &sec1_public_key(&env, &manager),
+        &2,
+    );
+    client.add_signer(
+        &credential_id(&env, &owner),
+        &sec1_public_key(&env, &owner),
+        &3,
+    );
+    client.set_threshold(&3);
+    assert_eq!(client.get_signer(&credential_id(&env, &manager)).weight, 2);
+
+    // The manager alone falls short, the owner alone or clerk + manager suffice
+    let payload = BytesN::random(&env);
+    let result = check_auth(&env, &contract_id, &payload, sign(&env, &manager, &payload));
+    assert_eq!(result, Err(Ok(Error::ThresholdNotMet)));
 
-    // Initialize with first key
-    client.init(&public_key1, &None).unwrap();
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &owner, &payload);
+    assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
 
-    // Verify first key is stored
-    assert_eq!(client.get_owner().unwrap(), public_key1);
-    
-    // Ensure second key is different
-    assert_ne!(public_key1, public_key2);
+    let payload = BytesN::random(&env);
+    let signatures = soroban_sdk::vec![
+        &env,
+        sign(&env, &clerk, &payload),
+        sign(&env, &manager, &payload)
+    ];
+    assert_eq!(
+        check_auth_signatures(&env, &contract_id, &payload, signatures),
+        Ok(())
+    );
+
+    // Zero-weight signers are rejected
+    let other = signing_key(10);
+    let result = client.try_add_signer(
+        &credential_id(&env, &other),
+        &sec1_public_key(&env, &other),
+        &0,
+    );
+    assert_eq!(result, Err(Ok(Error::InvalidWeight)));
+}
+
+#[test]
+fn test_threshold_must_stay_reachable() {
+    let env = Env::default();
+    let keys = [signing_key(7), signing_key(8), signing_key(9)];
+    let contract_id = setup_two_of_three(&env, &keys);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+
+    assert_eq!(
+        client.try_set_threshold(&0),
+        Err(Ok(Error::InvalidThreshold))
+    );
+    assert_eq!(
+        client.try_set_threshold(&4),
+        Err(Ok(Error::InvalidThreshold))
+    );
+
+    // 2 of 3 can lose one signer, but 2 of 2 cannot
+    client.remove_signer(&credential_id(&env, &keys[2]));
+    let result = client.try_remove_signer(&credential_id(&env, &keys[1]));
+    assert_eq!(result, Err(Ok(Error::InvalidThreshold)));
+    assert_eq!(client.list_signers().len(), 2);
 }
 
-// Note: Testing __check_auth requires more complex setup with actual signature generation
-// This would require secp256r1 signature creation which is typically done off-chain
 #[test]
 fn test_check_auth_not_initialized() {
     let env = Env::default();
-    let contract_id = env.register(PasskeyAccount, ());
-    
-    // Create a random signature and payload
-    let signature = BytesN::random(&env);
-    let payload = Hash::random(&env);
-    
+    let contract_id = env.register_contract(None, PasskeyAccount);
+
+    // Create a signature and payload
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &signing_key(7), &payload);
+
     // Try to authenticate without initialization
-    let result = env.try_invoke_contract_check_auth::<Error>(
+    let result = check_auth(&env, &contract_id, &payload, signature);
+
+    // Should fail because no owner is set
+    assert_eq!(result, Err(Ok(Error::NotInitialized)));
+}
+
+#[test]
+fn test_check_auth_valid_signature() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &key, &payload);
+
+    assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+}
+
+#[test]
+fn test_check_auth_wrong_key() {
+    let env = Env::default();
+    let contract_id = setup(&env, &signing_key(7));
+
+    // Sign with a key that is not the owner
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &signing_key(8), &payload);
+
+    assert!(check_auth(&env, &contract_id, &payload, signature).is_err());
+}
+
+#[test]
+fn test_check_auth_wrong_payload() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+
+    // An assertion over one payload must not authorize another
+    let signature = sign(&env, &key, &BytesN::random(&env));
+
+    let result = check_auth(&env, &contract_id, &BytesN::random(&env), signature);
+    assert_eq!(result, Err(Ok(Error::ChallengeMismatch)));
+}
+
+#[test]
+fn test_check_auth_replayed_assertion() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    // Encodes with `-` and `_`, so the standard alphabet gives a different challenge
+    let payload = BytesN::from_array(&env, &[0xfb; 32]);
+
+    // An assertion is bound to its payload, however it is replayed
+    let signature = sign_assertion(
+        &env,
+        &key,
+        authenticator_data_with(&env, b"localhost", 0x05, 1),
+        client_data_json(&payload).as_bytes(),
+    );
+    for other in [BytesN::random(&env), BytesN::from_array(&env, &[0u8; 32])] {
+        let result = check_auth(&env, &contract_id, &other, signature.clone());
+        assert_eq!(result, Err(Ok(Error::ChallengeMismatch)));
+    }
+    let mut flipped = payload.to_array();
+    flipped[31] ^= 1;
+    let result = check_auth(
+        &env,
+        &contract_id,
+        &BytesN::from_array(&env, &flipped),
+        signature.clone(),
+    );
+    assert_eq!(result, Err(Ok(Error::ChallengeMismatch)));
+
+    // It authorizes its own payload once; the counter stops it from being used again
+    assert_eq!(
+        check_auth(&env, &contract_id, &payload, signature.clone()),
+        Ok(())
+    );
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::SignCountNotIncreased)));
+
+    // Other encodings of the payload are not accepted as its challenge
+    let encoded = URL_SAFE_NO_PAD.encode(payload.to_array());
+    let standard = base64::engine::general_purpose::STANDARD.encode(payload.to_array());
+    for challenge in [format!("{encoded}="), standard, encoded[..42].into()] {
+        let json = format!(
+            r#"{{"type":"webauthn.get","challenge":"{challenge}","origin":"https://localhost"}}"#
+        );
+        let signature = sign_assertion(&env, &key, authenticator_data(&env), json.as_bytes());
+        let result = check_auth(&env, &contract_id, &payload, signature);
+        assert_eq!(result, Err(Ok(Error::ChallengeMismatch)));
+    }
+}
+
+#[test]
+fn test_check_auth_tampered_authenticator_data() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+
+    let payload = BytesN::random(&env);
+    let mut signature = sign(&env, &key, &payload);
+    signature.authenticator_data.set(32, 0x01);
+
+    assert!(check_auth(&env, &contract_id, &payload, signature).is_err());
+}
+
+#[test]
+fn test_check_auth_invalid_client_data() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let payload = BytesN::random(&env);
+
+    // clientDataJSON without a challenge
+    let signature = sign_assertion(
+        &env,
+        &key,
+        authenticator_data(&env),
+        br#"{"type":"webauthn.get","origin":"https://localhost"}"#,
+    );
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::InvalidClientData)));
+
+    // Oversized clientDataJSON is rejected rather than trapping
+    let mut json = client_data_json(&payload);
+    json.insert_str(1, &format!(r#""pad":"{}","#, "a".repeat(2048)));
+    let signature = sign_assertion(&env, &key, authenticator_data(&env), json.as_bytes());
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::ClientDataTooLarge)));
+}
+
+#[test]
+fn test_check_auth_malformed_signature() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let payload = BytesN::random(&env);
+
+    // An all-zero signature is rejected before reaching the host
+    let mut signature = sign(&env, &key, &payload);
+    signature.signature = BytesN::from_array(&env, &[0u8; 64]);
+    let result = check_auth(&env, &contract_id, &payload, signature.clone());
+    assert_eq!(result, Err(Ok(Error::InvalidSignature)));
+
+    // So is a signature whose `s` is not low-S normalized
+    let mut high_s = [0xffu8; 64];
+    high_s[0] = 0x01;
+    signature.signature = BytesN::from_array(&env, &high_s);
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::InvalidSignature)));
+}
+
+#[test]
+fn test_init_requires_an_origin() {
+    let env = Env::default();
+    let contract_id = env.register_contract(None, PasskeyAccount);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+
+    let result = client.try_init(
+        &sec1_public_key(&env, &signing_key(7)),
+        &Bytes::from_slice(&env, b"test-credential-id"),
+        &Vec::new(&env),
+        &rp_id(&env),
+        &true,
+    );
+    assert_eq!(result, Err(Ok(Error::EmptyOriginAllowList)));
+}
+
+#[test]
+fn test_check_auth_rejects_wrong_type() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let payload = BytesN::random(&env);
+
+    // An attestation (`webauthn.create`) response must not be usable as an assertion
+    let json = client_data_json(&payload).replace("webauthn.get", "webauthn.create");
+    let signature = sign_assertion(&env, &key, authenticator_data(&env), json.as_bytes());
+
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::InvalidClientDataType)));
+}
+
+#[test]
+fn test_check_auth_rejects_unknown_origin() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let payload = BytesN::random(&env);
+
+    // A passkey phished on another origin
+    let json = client_data_json(&payload).replace("https://localhost", "https://evil.example");
+    let signature = sign_assertion(&env, &key, authenticator_data(&env), json.as_bytes());
+
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::OriginNotAllowed)));
+}
+
+#[test]
+fn test_check_auth_cross_origin() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let payload = BytesN::random(&env);
+    let cross_origin =
+        client_data_json(&payload).replace(r#""crossOrigin":false"#, r#""crossOrigin":true"#);
+
+    // An allowed origin framed by an unknown page, or by a page that does not say
+    let framed_by = |top_origin: &str| {
+        let json = cross_origin.replace("}", &format!(r#","topOrigin":"{top_origin}"}}"#));
+        sign_assertion(&env, &key, authenticator_data(&env), json.as_bytes())
+    };
+    let result = check_auth(
+        &env,
         &contract_id,
         &payload,
-        signature.into_val(&env),
-        &soroban_sdk::vec![&env],
+        framed_by("https://evil.example"),
+    );
+    assert_eq!(result, Err(Ok(Error::OriginNotAllowed)));
+    let signature = sign_assertion(
+        &env,
+        &key,
+        authenticator_data(&env),
+        cross_origin.as_bytes(),
+    );
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::OriginNotAllowed)));
+
+    // Framed by an allowed page
+    let result = check_auth(&env, &contract_id, &payload, framed_by("https://localhost"));
+    assert_eq!(result, Ok(()));
+}
+
+#[test]
+fn test_manage_allowed_origins() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+
+    let localhost = Bytes::from_slice(&env, b"https://localhost");
+    let app = Bytes::from_slice(&env, b"https://app.example");
+
+    client.add_allowed_origin(&app);
+    assert_eq!(
+        env.auths()[0].0,
+        contract_id,
+        "adding an origin requires the account's own auth"
+    );
+    // Adding an origin twice keeps a single entry
+    client.add_allowed_origin(&app);
+    assert_eq!(
+        client.get_allowed_origins(),
+        soroban_sdk::vec![&env, localhost.clone(), app.clone()]
+    );
+
+    // Assertions from the new origin are accepted
+    let payload = BytesN::random(&env);
+    let json = client_data_json(&payload).replace("https://localhost", "https://app.example");
+    let signature = sign_assertion(&env, &key, authenticator_data(&env), json.as_bytes());
+    assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+
+    // Removing the old origin stops accepting assertions from it
+    client.remove_allowed_origin(&localhost);
+    assert_eq!(
+        client.get_allowed_origins(),
+        soroban_sdk::vec![&env, app.clone()]
+    );
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &key, &payload);
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::OriginNotAllowed)));
+
+    // The last origin cannot be removed
+    let result = client.try_remove_allowed_origin(&app);
+    assert_eq!(result, Err(Ok(Error::EmptyOriginAllowList)));
+}
+
+#[test]
+fn test_check_auth_rejects_other_relying_party() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let payload = BytesN::random(&env);
+
+    let data = authenticator_data_with(&env, b"evil.example", 0x05, 0);
+    let signature = sign_assertion(&env, &key, data, client_data_json(&payload).as_bytes());
+
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::RpIdHashMismatch)));
+}
+
+#[test]
+fn test_check_auth_rejects_short_authenticator_data() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let payload = BytesN::random(&env);
+
+    let data = authenticator_data(&env).slice(..36);
+    let signature = sign_assertion(&env, &key, data, client_data_json(&payload).as_bytes());
+
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::InvalidAuthenticatorData)));
+}
+
+#[test]
+fn test_check_auth_requires_user_presence() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let payload = BytesN::random(&env);
+
+    // UV without UP
+    let data = authenticator_data_with(&env, b"localhost", 0x04, 0);
+    let signature = sign_assertion(&env, &key, data, client_data_json(&payload).as_bytes());
+
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::UserNotPresent)));
+}
+
+#[test]
+fn test_check_auth_user_verification_policy() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    assert!(client.get_user_verification_required());
+
+    // UP only: rejected while the account requires biometrics
+    let payload = BytesN::random(&env);
+    let data = authenticator_data_with(&env, b"localhost", 0x01, 0);
+    let signature = sign_assertion(&env, &key, data, client_data_json(&payload).as_bytes());
+    let result = check_auth(&env, &contract_id, &payload, signature.clone());
+    assert_eq!(result, Err(Ok(Error::UserNotVerified)));
+
+    // ...and accepted once the policy is relaxed
+    client.set_user_verification_required(&false);
+    assert!(!client.get_user_verification_required());
+    assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+}
+
+#[test]
+fn test_check_auth_backup_policy() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let credential_id = credential_id(&env, &key);
+    assert_eq!(client.get_backup_policy(&credential_id), BackupPolicy::Any);
+
+    let check_auth_with = |flags: u8| {
+        let payload = BytesN::random(&env);
+        let data = authenticator_data_with(&env, b"localhost", flags, 0);
+        let signature = sign_assertion(&env, &key, data, client_data_json(&payload).as_bytes());
+        check_auth(&env, &contract_id, &payload, signature)
+    };
+    // UP and UV, plus BE for an eligible passkey and BE and BS for a synced one
+    let (device_bound, eligible, synced) = (0x05, 0x0d, 0x1d);
+    let not_allowed = Err(Ok(Error::BackupStateNotAllowed));
+
+    for flags in [device_bound, eligible, synced] {
+        assert_eq!(check_auth_with(flags), Ok(()));
+    }
+    // BS without BE is not a valid combination
+    assert_eq!(
+        check_auth_with(0x15),
+        Err(Ok(Error::InvalidAuthenticatorData))
+    );
+
+    client.set_backup_policy(&credential_id, &BackupPolicy::DeviceBound);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (
+            Symbol::new(&env, "backup_policy_set"),
+            credential_id.clone(),
+        ),
+        BackupPolicy::DeviceBound,
+    );
+    assert_eq!(check_auth_with(device_bound), Ok(()));
+    assert_eq!(check_auth_with(eligible), not_allowed);
+    assert_eq!(check_auth_with(synced), not_allowed);
+
+    client.set_backup_policy(&credential_id, &BackupPolicy::Synced);
+    assert_eq!(check_auth_with(device_bound), not_allowed);
+    assert_eq!(check_auth_with(eligible), not_allowed);
+    assert_eq!(check_auth_with(synced), Ok(()));
+
+    client.set_backup_policy(&credential_id, &BackupPolicy::Any);
+    assert_eq!(client.get_backup_policy(&credential_id), BackupPolicy::Any);
+    assert_eq!(check_auth_with(eligible), Ok(()));
+
+    let result = client.try_set_backup_policy(&Bytes::new(&env), &BackupPolicy::Synced);
+    assert_eq!(result, Err(Ok(Error::SignerNotFound)));
+}
+
+#[test]
+fn test_check_auth_sign_count() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+
+    let assert_with_counter = |counter: u32| {
+        let payload = BytesN::random(&env);
+        let data = authenticator_data_with(&env, b"localhost", 0x05, counter);
+        let signature = sign_assertion(&env, &key, data, client_data_json(&payload).as_bytes());
+        check_auth(&env, &contract_id, &payload, signature)
+    };
+
+    // Increasing counters are accepted and recorded
+    assert_eq!(assert_with_counter(1), Ok(()));
+    assert_eq!(assert_with_counter(5), Ok(()));
+    let sign_count = || client.get_signer(&credential_id(&env, &key)).sign_count;
+    assert_eq!(sign_count(), 5);
+
+    // A repeated, lower or reset counter points to a cloned authenticator
+    assert_eq!(
+        assert_with_counter(5),
+        Err(Ok(Error::SignCountNotIncreased))
+    );
+    assert_eq!(
+        assert_with_counter(3),
+        Err(Ok(Error::SignCountNotIncreased))
+    );
+    assert_eq!(
+        assert_with_counter(0),
+        Err(Ok(Error::SignCountNotIncreased))
+    );
+    assert_eq!(sign_count(), 5);
+}
+
+/// What a passkey signs to report its own counter as not increasing
+fn clone_report_challenge(
+    env: &Env,
+    contract_id: &Address,
+    credential_id: &Bytes,
+    stored_sign_count: u32,
+) -> BytesN<32> {
+    let mut message = Bytes::from_slice(env, b"report_clone");
+    message.append(&contract_id.clone().to_xdr(env));
+    message.append(credential_id);
+    message.extend_from_array(&stored_sign_count.to_be_bytes());
+    env.crypto().sha256(&message).to_bytes()
+}
+
+#[test]
+fn test_report_clone() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let credential_id = credential_id(&env, &key);
+
+    let report_with_counter = |stored: u32, counter: u32| {
+        let challenge = clone_report_challenge(&env, &contract_id, &credential_id, stored);
+        let data = authenticator_data_with(&env, b"localhost", 0x05, counter);
+        let assertion = sign_assertion(&env, &key, data, client_data_json(&challenge).as_bytes());
+        client.try_report_clone(&assertion)
+    };
+
+    // A counter that increased is recorded, not reported
+    assert_eq!(report_with_counter(0, 5), Ok(Ok(false)));
+    assert_eq!(client.get_signer(&credential_id).sign_count, 5);
+    assert_eq!(client.get_clone_report(&credential_id), None);
+
+    // Assertions for an older counter, or for a transaction, are not reports
+    assert_eq!(report_with_counter(0, 3), Err(Ok(Error::ChallengeMismatch)));
+    let payload = BytesN::random(&env);
+    assert_eq!(
+        client.try_report_clone(&sign(&env, &key, &payload)),
+        Err(Ok(Error::ChallengeMismatch))
+    );
+
+    // A counter that did not increase is recorded and published
+    assert_eq!(report_with_counter(5, 3), Ok(Ok(true)));
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "clone_detected"), credential_id.clone()),
+        (5u32, 3u32),
+    );
+    let report = CloneReport {
+        stored_sign_count: 5,
+        sign_count: 3,
+        ledger: env.ledger().sequence(),
+    };
+    assert_eq!(client.get_clone_report(&credential_id), Some(report));
+    assert_eq!(client.get_signer(&credential_id).sign_count, 5);
+
+    let result = client.try_report_clone(&sign(&env, &signing_key(8), &payload));
+    assert_eq!(result, Err(Ok(Error::SignerNotFound)));
+}
+
+#[test]
+fn test_check_auth_zero_sign_count() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+
+    // Authenticators without a counter (e.g. synced passkeys) always report 0
+    for _ in 0..3 {
+        let payload = BytesN::random(&env);
+        let signature = sign(&env, &key, &payload);
+        assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+    }
+    assert_eq!(client.get_signer(&credential_id(&env, &key)).sign_count, 0);
+}
+
+/// Asserts the most recent event was published by the account with these topics and data
+fn assert_last_event(
+    env: &Env,
+    contract_id: &Address,
+    topics: impl IntoVal<Env, Vec<soroban_sdk::Val>>,
+    data: impl IntoVal<Env, soroban_sdk::Val>,
+) {
+    let events = env.events().all();
+    assert_eq!(
+        events.slice(events.len() - 1..),
+        soroban_sdk::vec![
+            env,
+            (
+                contract_id.clone(),
+                topics.into_val(env),
+                data.into_val(env)
+            )
+        ]
+    );
+}
+
+/// Three guardians, two of which must approve a recovery that waits 72 hours
+fn recovery_config(env: &Env) -> RecoveryConfig {
+    RecoveryConfig {
+        guardians: soroban_sdk::vec![
+            env,
+            Address::generate(env),
+            Address::generate(env),
+            Address::generate(env)
+        ],
+        quorum: 2,
+        delay: 72 * 60 * 60,
+    }
+}
+
+#[test]
+fn test_set_recovery_config() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    assert_eq!(client.get_recovery_config(), None);
+
+    let config = recovery_config(&env);
+    client.set_recovery_config(&config);
+    assert_eq!(
+        env.auths()[0].0,
+        contract_id,
+        "designating guardians requires the account's own auth"
+    );
+    assert_eq!(client.get_recovery_config(), Some(config.clone()));
+
+    // The quorum must be reachable and guardians unique
+    for quorum in [0, 4] {
+        let result = client.try_set_recovery_config(&RecoveryConfig {
+            quorum,
+            ..config.clone()
+        });
+        assert_eq!(result, Err(Ok(Error::InvalidGuardians)));
+    }
+    let mut guardians = config.guardians.clone();
+    guardians.push_back(config.guardians.get(0).unwrap());
+    let result = client.try_set_recovery_config(&RecoveryConfig {
+        guardians,
+        ..config.clone()
+    });
+    assert_eq!(result, Err(Ok(Error::InvalidGuardians)));
+
+    // The signers must be left a window to cancel a recovery
+    for delay in [0, 24 * 60 * 60 - 1] {
+        let result = client.try_set_recovery_config(&RecoveryConfig {
+            delay,
+            ..config.clone()
+        });
+        assert_eq!(result, Err(Ok(Error::InvalidGuardians)));
+    }
+}
+
+#[test]
+fn test_recovery() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let lost = signing_key(7);
+    let new = signing_key(8);
+    let contract_id = setup(&env, &lost);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let config = recovery_config(&env);
+    client.set_recovery_config(&config);
+    let [first, second, _] = [0, 1, 2].map(|i| config.guardians.get(i).unwrap());
+    let new_credential = credential_id(&env, &new);
+
+    // Whoever took the lost passkey registered a session key with it
+    let session = ed25519_session_key(1);
+    let game = Address::generate(&env);
+    let allowed_calls = soroban_sdk::vec![
+        &env,
+        AllowedCall {
+            contract: game.clone(),
+            functions: Vec::new(&env),
+        }
+    ];
+    client.add_session_key(
+        &ed25519_session_public_key(&env, &session),
+        &(env.ledger().sequence() + 1000),
+        &allowed_calls,
+        &Map::new(&env),
+    );
+
+    // One guardian starts the replacement...
+    client.start_recovery(&first, &new_credential, &sec1_public_key(&env, &new));
+    assert_eq!(env.auths()[0].0, first);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "recovery_started"), first.clone()),
+        (new_credential.clone(), config.delay),
+    );
+    assert_eq!(
+        client.try_execute_recovery(),
+        Err(Ok(Error::RecoveryNotReady))
+    );
+
+    // ...a second one approves it, whichever encoding of the key they were given...
+    let raw = Bytes::from(public_key(&env, &new));
+    client.approve_recovery(&second, &new_credential, &raw);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "recovery_approved"), second.clone()),
+        2u32,
+    );
+    assert_eq!(
+        client.try_execute_recovery(),
+        Err(Ok(Error::RecoveryNotReady))
+    );
+
+    // ...and it runs once the timelock expires
+    env.ledger()
+        .with_mut(|ledger| ledger.timestamp += config.delay);
+    client.execute_recovery();
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "recovery_completed"), new_credential),
+        public_key(&env, &new),
+    );
+    assert_eq!(client.get_recovery(), None);
+    assert_eq!(
+        client.list_signers(),
+        soroban_sdk::vec![&env, credential_id(&env, &new)]
+    );
+    assert_eq!(client.get_threshold(), 1);
+
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &new, &payload);
+    assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+    let signature = sign(&env, &lost, &payload);
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::SignerNotFound)));
+
+    // The recovery revoked the session key
+    assert_eq!(client.list_session_keys().len(), 0);
+    let authorization = sign_ed25519_session(&env, &session, &payload);
+    let calls = soroban_sdk::vec![&env, contract_call(&env, &game, "move", Vec::new(&env))];
+    let result = check_auth_for(&env, &contract_id, &payload, authorization, calls);
+    assert_eq!(result, Err(Ok(Error::SessionKeyNotFound)));
+}
+
+#[test]
+fn test_recovery_requires_guardians() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let new = signing_key(8);
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let config = recovery_config(&env);
+    client.set_recovery_config(&config);
+    let guardian = config.guardians.get(0).unwrap();
+    let (new_credential, new_key) = (credential_id(&env, &new), sec1_public_key(&env, &new));
+
+    // Strangers cannot start or approve a recovery
+    let stranger = Address::generate(&env);
+    let result = client.try_start_recovery(&stranger, &new_credential, &new_key);
+    assert_eq!(result, Err(Ok(Error::NotGuardian)));
+    let result = client.try_approve_recovery(&guardian, &new_credential, &new_key);
+    assert_eq!(result, Err(Ok(Error::NoRecoveryInProgress)));
+
+    // Only one recovery runs at a time, and approvals must name its key
+    client.start_recovery(&guardian, &new_credential, &new_key);
+    let result = client.try_start_recovery(&guardian, &new_credential, &new_key);
+    assert_eq!(result, Err(Ok(Error::RecoveryInProgress)));
+    let other = signing_key(9);
+    let result = client.try_approve_recovery(
+        &config.guardians.get(1).unwrap(),
+        &new_credential,
+        &sec1_public_key(&env, &other),
+    );
+    assert_eq!(result, Err(Ok(Error::RecoveryMismatch)));
+    let result = client.try_approve_recovery(&stranger, &new_credential, &new_key);
+    assert_eq!(result, Err(Ok(Error::NotGuardian)));
+}
+
+#[test]
+fn test_cancel_recovery() {
+    let env = Env::default();
+    let keys = [signing_key(7), signing_key(8), signing_key(9)];
+    let contract_id = setup_two_of_three(&env, &keys);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let config = recovery_config(&env);
+    client.set_recovery_config(&config);
+    let new = signing_key(10);
+    client.start_recovery(
+        &config.guardians.get(0).unwrap(),
+        &credential_id(&env, &new),
+        &sec1_public_key(&env, &new),
+    );
+
+    // A single signer of the 2-of-3 account can authorize the cancellation, but nothing else
+    let cancel = contract_call(&env, &contract_id, "cancel_recovery", Vec::new(&env));
+    let payload = BytesN::random(&env);
+    let result = check_auth_for(
+        &env,
+        &contract_id,
+        &payload,
+        Authorization::Passkeys(soroban_sdk::vec![&env, sign(&env, &keys[1], &payload)]),
+        soroban_sdk::vec![&env, cancel.clone()],
+    );
+    assert_eq!(result, Ok(()));
+    let payload = BytesN::random(&env);
+    let other = contract_call(&env, &contract_id, "remove_signer", Vec::new(&env));
+    let result = check_auth_for(
+        &env,
+        &contract_id,
+        &payload,
+        Authorization::Passkeys(soroban_sdk::vec![&env, sign(&env, &keys[1], &payload)]),
+        soroban_sdk::vec![&env, cancel, other],
+    );
+    assert_eq!(result, Err(Ok(Error::ThresholdNotMet)));
+
+    client.cancel_recovery();
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "recovery_cancelled"),),
+        credential_id(&env, &new),
+    );
+    assert_eq!(client.get_recovery(), None);
+    assert_eq!(
+        client.try_execute_recovery(),
+        Err(Ok(Error::NoRecoveryInProgress))
+    );
+    assert_eq!(
+        client.try_cancel_recovery(),
+        Err(Ok(Error::NoRecoveryInProgress))
+    );
+}
+
+/// A call to `fn_name` on `contract` being authorized
+fn contract_call(
+    env: &Env,
+    contract: &Address,
+    fn_name: &str,
+    args: Vec<soroban_sdk::Val>,
+) -> Context {
+    Context::Contract(soroban_sdk::auth::ContractContext {
+        contract: contract.clone(),
+        fn_name: Symbol::new(env, fn_name),
+        args,
+    })
+}
+
+/// A token `transfer` of `amount` from the account
+fn transfer(env: &Env, token: &Address, account: &Address, amount: i128) -> Context {
+    let args = (account.clone(), Address::generate(env), amount).into_val(env);
+    contract_call(env, token, "transfer", args)
+}
+
+/// Deterministic ed25519 session key
+fn ed25519_session_key(seed: u8) -> ed25519_dalek::SigningKey {
+    ed25519_dalek::SigningKey::from_bytes(&[seed; 32])
+}
+
+fn ed25519_session_public_key(env: &Env, key: &ed25519_dalek::SigningKey) -> SessionPublicKey {
+    SessionPublicKey::Ed25519(BytesN::from_array(env, &key.verifying_key().to_bytes()))
+}
+
+/// Signs a Soroban signature payload with an ed25519 session key
+fn sign_ed25519_session(
+    env: &Env,
+    key: &ed25519_dalek::SigningKey,
+    payload: &BytesN<32>,
+) -> Authorization {
+    use ed25519_dalek::Signer as _;
+    Authorization::SessionKey(SessionSignature {
+        public_key: ed25519_session_public_key(env, key),
+        signature: BytesN::from_array(env, &key.sign(&payload.to_array()).to_bytes()),
+    })
+}
+
+#[test]
+fn test_session_key_ed25519() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let [token, other_token] = [Address::generate(&env), Address::generate(&env)];
+    let session = ed25519_session_key(1);
+    let public_key = ed25519_session_public_key(&env, &session);
+
+    // The dApp may transfer up to 100 of each of two tokens for the next 100 ledgers
+    let expiration_ledger = env.ledger().sequence() + 100;
+    let transfers = |token: &Address| AllowedCall {
+        contract: token.clone(),
+        functions: soroban_sdk::vec![&env, Symbol::new(&env, "transfer")],
+    };
+    let allowed_calls = soroban_sdk::vec![&env, transfers(&token), transfers(&other_token)];
+    let spend_limits = soroban_sdk::map![&env, (token.clone(), 100), (other_token.clone(), 100)];
+    client.add_session_key(
+        &public_key,
+        &expiration_ledger,
+        &allowed_calls,
+        &spend_limits,
+    );
+    assert_eq!(
+        env.auths()[0].0,
+        contract_id,
+        "registering a session key requires the account's own auth"
+    );
+
+    let payload = BytesN::random(&env);
+    let authorization = sign_ed25519_session(&env, &session, &payload);
+    let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 60)];
+    assert_eq!(
+        check_auth_for(&env, &contract_id, &payload, authorization, calls),
+        Ok(())
+    );
+    assert_eq!(
+        client.list_session_keys(),
+        soroban_sdk::vec![
+            &env,
+            SessionKey {
+                public_key,
+                expiration_ledger,
+                allowed_calls,
+                spend_limits,
+                spent: soroban_sdk::map![&env, (token.clone(), 60)],
+            }
+        ]
+    );
+
+    // The spend cap is cumulative, and negative amounts do not give budget back
+    let payload = BytesN::random(&env);
+    let authorization = sign_ed25519_session(&env, &session, &payload);
+    let calls = soroban_sdk::vec![
+        &env,
+        transfer(&env, &token, &contract_id, -50),
+        transfer(&env, &token, &contract_id, 50)
+    ];
+    let result = check_auth_for(&env, &contract_id, &payload, authorization, calls);
+    assert_eq!(result, Err(Ok(Error::SpendLimitExceeded)));
+
+    // Each token has its own budget
+    let payload = BytesN::random(&env);
+    let authorization = sign_ed25519_session(&env, &session, &payload);
+    let calls = soroban_sdk::vec![&env, transfer(&env, &other_token, &contract_id, 100)];
+    assert_eq!(
+        check_auth_for(&env, &contract_id, &payload, authorization, calls),
+        Ok(())
+    );
+
+    // Other functions, other contracts and the account itself are out of scope
+    for call in [
+        contract_call(&env, &token, "burn", Vec::new(&env)),
+        transfer(&env, &Address::generate(&env), &contract_id, 1),
+        contract_call(&env, &contract_id, "add_signer", Vec::new(&env)),
+    ] {
+        let payload = BytesN::random(&env);
+        let authorization = sign_ed25519_session(&env, &session, &payload);
+        let calls = soroban_sdk::vec![&env, call];
+        let result = check_auth_for(&env, &contract_id, &payload, authorization, calls);
+        assert_eq!(result, Err(Ok(Error::SessionKeyOutOfScope)));
+    }
+}
+
+#[test]
+fn test_session_key_secp256r1() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let game = Address::generate(&env);
+    let session = signing_key(20);
+    let public_key = SessionPublicKey::Secp256r1(public_key(&env, &session));
+
+    // Any function of the game contract, without a spend cap
+    let allowed_calls = soroban_sdk::vec![
+        &env,
+        AllowedCall {
+            contract: game.clone(),
+            functions: Vec::new(&env),
+        }
+    ];
+    let expiration_ledger = env.ledger().sequence() + 100;
+    client.add_session_key(
+        &public_key,
+        &expiration_ledger,
+        &allowed_calls,
+        &Map::new(&env),
+    );
+
+    // Session keys sign the payload itself, not a WebAuthn assertion
+    let payload = BytesN::random(&env);
+    let signature: p256::ecdsa::Signature = session.sign_prehash(&payload.to_array()).unwrap();
+    let signature = signature.normalize_s().unwrap_or(signature);
+    let authorization = Authorization::SessionKey(SessionSignature {
+        public_key,
+        signature: BytesN::from_array(&env, &signature.to_bytes().into()),
+    });
+    let calls = soroban_sdk::vec![
+        &env,
+        contract_call(&env, &game, "move", Vec::new(&env)),
+        transfer(&env, &game, &contract_id, 1_000_000)
+    ];
+    assert_eq!(
+        check_auth_for(&env, &contract_id, &payload, authorization, calls),
+        Ok(())
+    );
+}
+
+#[test]
+fn test_session_key_unreadable_amount() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let nft = Address::generate(&env);
+    let allowed_calls = soroban_sdk::vec![
+        &env,
+        AllowedCall {
+            contract: nft.clone(),
+            functions: Vec::new(&env),
+        }
+    ];
+    let expiration_ledger = env.ledger().sequence() + 100;
+    let [uncapped, capped] = [ed25519_session_key(1), ed25519_session_key(2)];
+    let caps = [Map::new(&env), soroban_sdk::map![&env, (nft.clone(), 100)]];
+    for (key, spend_limits) in [(&uncapped, &caps[0]), (&capped, &caps[1])] {
+        let public_key = ed25519_session_public_key(&env, key);
+        client.add_session_key(
+            &public_key,
+            &expiration_ledger,
+            &allowed_calls,
+            spend_limits,
+        );
+    }
+
+    // An NFT `transfer(from, to, token_id: u32)` has no i128 amount to read
+    let args = (contract_id.clone(), Address::generate(&env), 7u32).into_val(&env);
+    let nft_transfer = contract_call(&env, &nft, "transfer", args);
+    let authorize = |key: &ed25519_dalek::SigningKey, call: &Context| {
+        let payload = BytesN::random(&env);
+        let authorization = sign_ed25519_session(&env, key, &payload);
+        let calls = soroban_sdk::vec![&env, call.clone()];
+        check_auth_for(&env, &contract_id, &payload, authorization, calls)
+    };
+
+    // It does not use up a key without a spend limit...
+    for _ in 0..2 {
+        assert_eq!(authorize(&uncapped, &nft_transfer), Ok(()));
+    }
+    let token_transfer = transfer(&env, &nft, &contract_id, 1_000);
+    assert_eq!(authorize(&uncapped, &token_transfer), Ok(()));
+
+    // ...and exceeds a key's spend limit without counting towards it
+    assert_eq!(
+        authorize(&capped, &nft_transfer),
+        Err(Ok(Error::SpendLimitExceeded))
+    );
+    let spent = |key: &ed25519_dalek::SigningKey| {
+        let public_key = ed25519_session_public_key(&env, key);
+        let session_keys = client.list_session_keys();
+        session_keys
+            .iter()
+            .find(|session_key| session_key.public_key == public_key)
+            .unwrap()
+            .spent
+            .get(nft.clone())
+            .unwrap_or(0)
+    };
+    assert_eq!(spent(&uncapped), 0);
+    assert_eq!(spent(&capped), 0);
+}
+
+#[test]
+fn test_session_key_expiry_and_revocation() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let game = Address::generate(&env);
+    let allowed_calls = soroban_sdk::vec![
+        &env,
+        AllowedCall {
+            contract: game.clone(),
+            functions: Vec::new(&env),
+        }
+    ];
+    let [short, long] = [ed25519_session_key(1), ed25519_session_key(2)];
+    let sequence = env.ledger().sequence();
+    for (key, lifetime) in [(&short, 10), (&long, 1000)] {
+        let public_key = ed25519_session_public_key(&env, key);
+        client.add_session_key(
+            &public_key,
+            &(sequence + lifetime),
+            &allowed_calls,
+            &Map::new(&env),
+        );
+    }
+    assert_eq!(client.list_session_keys().len(), 2);
+
+    // Expired keys are no longer listed nor accepted
+    env.ledger().with_mut(|ledger| ledger.sequence_number += 11);
+    let active = client.list_session_keys();
+    assert_eq!(active.len(), 1);
+    assert_eq!(
+        active.get(0).unwrap().public_key,
+        ed25519_session_public_key(&env, &long)
+    );
+    let payload = BytesN::random(&env);
+    let authorization = sign_ed25519_session(&env, &short, &payload);
+    let calls = soroban_sdk::vec![&env, contract_call(&env, &game, "move", Vec::new(&env))];
+    let result = check_auth_for(&env, &contract_id, &payload, authorization, calls.clone());
+    assert_eq!(result, Err(Ok(Error::SessionKeyExpired)));
+
+    // Revoked keys are gone
+    let public_key = ed25519_session_public_key(&env, &long);
+    client.revoke_session_key(&public_key);
+    assert_eq!(client.list_session_keys().len(), 0);
+    let authorization = sign_ed25519_session(&env, &long, &payload);
+    let result = check_auth_for(&env, &contract_id, &payload, authorization, calls);
+    assert_eq!(result, Err(Ok(Error::SessionKeyNotFound)));
+    let result = client.try_revoke_session_key(&public_key);
+    assert_eq!(result, Err(Ok(Error::SessionKeyNotFound)));
+
+    // Registering another key removes the expired one from storage
+    let expired = ed25519_session_public_key(&env, &short);
+    let public_key = ed25519_session_public_key(&env, &ed25519_session_key(3));
+    let expiration_ledger = env.ledger().sequence() + 10;
+    client.add_session_key(
+        &public_key,
+        &expiration_ledger,
+        &allowed_calls,
+        &Map::new(&env),
+    );
+    env.as_contract(&contract_id, || {
+        assert_eq!(session_key_ids(&env), soroban_sdk::vec![&env, public_key]);
+        assert_eq!(get_session_key(&env, &expired), None);
+    });
+}
+
+#[test]
+fn test_add_session_key_validation() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().with_mut(|ledger| ledger.sequence_number = 100);
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let public_key = ed25519_session_public_key(&env, &ed25519_session_key(1));
+    let calls_to = |contract: &Address| {
+        soroban_sdk::vec![
+            &env,
+            AllowedCall {
+                contract: contract.clone(),
+                functions: Vec::new(&env),
+            }
+        ]
+    };
+    let token = Address::generate(&env);
+
+    for (expiration_ledger, allowed_calls, spend_limits) in [
+        (99, calls_to(&token), Map::new(&env)),
+        (200, calls_to(&contract_id), Map::new(&env)),
+        (
+            200,
+            calls_to(&token),
+            soroban_sdk::map![&env, (token.clone(), -1)],
+        ),
+    ] {
+        let result = client.try_add_session_key(
+            &public_key,
+            &expiration_ledger,
+            &allowed_calls,
+            &spend_limits,
+        );
+        assert_eq!(result, Err(Ok(Error::InvalidSessionKey)));
+    }
+}
+
+#[test]
+fn test_signer_policy() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let owner = signing_key(7);
+    let employee = signing_key(8);
+    let contract_id = setup(&env, &owner);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let employee_id = credential_id(&env, &employee);
+    client.add_signer(&employee_id, &sec1_public_key(&env, &employee), &1);
+
+    // The employee may only transfer up to 100 of one token
+    let token = Address::generate(&env);
+    let policy = Policy {
+        allowed_calls: soroban_sdk::vec![
+            &env,
+            AllowedCall {
+                contract: token.clone(),
+                functions: soroban_sdk::vec![&env, Symbol::new(&env, "transfer")],
+            }
+        ],
+        max_amount: Some(100),
+    };
+    client.set_signer_policy(&employee_id, &policy);
+    assert_eq!(
+        env.auths()[0].0,
+        contract_id,
+        "restricting a signer requires the account's own auth"
+    );
+    assert_eq!(client.get_signer_policy(&employee_id), Some(policy));
+
+    let check = |key: &SigningKey, call: Context| {
+        let payload = BytesN::random(&env);
+        let signatures = soroban_sdk::vec![&env, sign(&env, key, &payload)];
+        check_auth_for(
+            &env,
+            &contract_id,
+            &payload,
+            Authorization::Passkeys(signatures),
+            soroban_sdk::vec![&env, call],
+        )
+    };
+    assert_eq!(
+        check(&employee, transfer(&env, &token, &contract_id, 100)),
+        Ok(())
+    );
+    for call in [
+        transfer(&env, &token, &contract_id, 101),
+        contract_call(&env, &token, "burn", Vec::new(&env)),
+        transfer(&env, &Address::generate(&env), &contract_id, 1),
+        contract_call(&env, &contract_id, "add_signer", Vec::new(&env)),
+    ] {
+        assert_eq!(
+            check(&employee, call.clone()),
+            Err(Ok(Error::ContextNotAllowed))
+        );
+        // The unrestricted owner may still authorize anything
+        assert_eq!(check(&owner, call), Ok(()));
+    }
+
+    // Lifting the policy makes the employee unrestricted again
+    client.remove_signer_policy(&employee_id);
+    assert_eq!(client.get_signer_policy(&employee_id), None);
+    assert_eq!(
+        check(&employee, transfer(&env, &token, &contract_id, 101)),
+        Ok(())
+    );
+
+    let result = client.try_set_signer_policy(
+        &Bytes::from_slice(&env, b"unknown"),
+        &Policy {
+            allowed_calls: Vec::new(&env),
+            max_amount: None,
+        },
+    );
+    assert_eq!(result, Err(Ok(Error::SignerNotFound)));
+}
+
+#[test]
+fn test_signer_policy_with_threshold() {
+    let env = Env::default();
+    let keys = [signing_key(7), signing_key(8), signing_key(9)];
+    let contract_id = setup_two_of_three(&env, &keys);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+
+    // One of the three signers may not call the account itself (the default for a policy)
+    client.set_signer_policy(
+        &credential_id(&env, &keys[2]),
+        &Policy {
+            allowed_calls: Vec::new(&env),
+            max_amount: None,
+        },
+    );
+
+    let check = |signers: [&SigningKey; 2], call: Context| {
+        let payload = BytesN::random(&env);
+        let signatures = soroban_sdk::vec![
+            &env,
+            sign(&env, signers[0], &payload),
+            sign(&env, signers[1], &payload)
+        ];
+        check_auth_for(
+            &env,
+            &contract_id,
+            &payload,
+            Authorization::Passkeys(signatures),
+            soroban_sdk::vec![&env, call],
+        )
+    };
+
+    // Its weight counts towards other calls, but not towards managing the account
+    let token = Address::generate(&env);
+    let manage = contract_call(&env, &contract_id, "set_threshold", Vec::new(&env));
+    assert_eq!(
+        check(
+            [&keys[0], &keys[2]],
+            transfer(&env, &token, &contract_id, 1)
+        ),
+        Ok(())
+    );
+    assert_eq!(
+        check([&keys[0], &keys[2]], manage.clone()),
+        Err(Ok(Error::ContextNotAllowed))
+    );
+    assert_eq!(check([&keys[0], &keys[1]], manage), Ok(()));
+}
+
+/// Authorizes `calls` with assertions from each of `keys`
+fn check_calls(
+    env: &Env,
+    contract_id: &Address,
+    keys: &[&SigningKey],
+    calls: Vec<Context>,
+) -> Result<(), Result<Error, soroban_sdk::InvokeError>> {
+    let payload = BytesN::random(env);
+    let mut signatures = Vec::new(env);
+    for key in keys {
+        signatures.push_back(sign(env, key, &payload));
+    }
+    check_auth_for(
+        env,
+        contract_id,
+        &payload,
+        Authorization::Passkeys(signatures),
+        calls,
+    )
+}
+
+#[test]
+fn test_daily_spend_limit() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let [worker, partner, admin] = [signing_key(7), signing_key(8), signing_key(9)];
+    let contract_id = setup(&env, &worker);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    client.add_signer(
+        &credential_id(&env, &partner),
+        &sec1_public_key(&env, &partner),
+        &1,
+    );
+    client.add_signer(
+        &credential_id(&env, &admin),
+        &sec1_public_key(&env, &admin),
+        &2,
+    );
+
+    // 100 per day, unless signers with a total weight of 2 agree
+    let token = Address::generate(&env);
+    let spend_limit = SpendLimit {
+        limit: 100,
+        override_weight: 2,
+    };
+    client.set_spend_limit(&token, &spend_limit);
+    assert_eq!(
+        env.auths()[0].0,
+        contract_id,
+        "setting a spend limit requires the account's own auth"
+    );
+    assert_eq!(client.get_spend_limit(&token), Some(spend_limit));
+
+    // Transfers and approvals add up
+    let approve = |amount: i128| {
+        let args = (
+            contract_id.clone(),
+            Address::generate(&env),
+            amount,
+            1000u32,
+        )
+            .into_val(&env);
+        contract_call(&env, &token, "approve", args)
+    };
+    let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 60), approve(30)];
+    assert_eq!(check_calls(&env, &contract_id, &[&worker], calls), Ok(()));
+    assert_eq!(client.get_spent(&token), 90);
+    let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 11)];
+    assert_eq!(
+        check_calls(&env, &contract_id, &[&worker], calls),
+        Err(Ok(Error::DailyLimitExceeded))
+    );
+
+    // Other tokens are not limited
+    let other_token = Address::generate(&env);
+    let calls = soroban_sdk::vec![&env, transfer(&env, &other_token, &contract_id, 1_000)];
+    assert_eq!(check_calls(&env, &contract_id, &[&worker], calls), Ok(()));
+
+    // The admin passkey, or a quorum of the others, can exceed the limit
+    let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 500)];
+    assert_eq!(check_calls(&env, &contract_id, &[&admin], calls), Ok(()));
+    let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 500)];
+    assert_eq!(
+        check_calls(&env, &contract_id, &[&worker, &partner], calls),
+        Ok(())
+    );
+    assert_eq!(client.get_spent(&token), 1_090);
+
+    // The window rolls over once 24 hours have passed since the end of the spends' hour
+    env.ledger()
+        .with_mut(|ledger| ledger.timestamp += 25 * 60 * 60);
+    assert_eq!(client.get_spent(&token), 0);
+    let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 100)];
+    assert_eq!(check_calls(&env, &contract_id, &[&worker], calls), Ok(()));
+
+    // Removing the limit lifts it
+    client.remove_spend_limit(&token);
+    assert_eq!(client.get_spend_limit(&token), None);
+    let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 1_000)];
+    assert_eq!(check_calls(&env, &contract_id, &[&worker], calls), Ok(()));
+}
+
+#[test]
+fn test_daily_spend_limit_rolls() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let token = Address::generate(&env);
+    client.set_spend_limit(
+        &token,
+        &SpendLimit {
+            limit: 100,
+            override_weight: 2,
+        },
+    );
+
+    // 60 now and 40 twelve hours later; only the first expires 25 hours from now
+    for amount in [60, 40] {
+        let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, amount)];
+        assert_eq!(check_calls(&env, &contract_id, &[&key], calls), Ok(()));
+        env.ledger()
+            .with_mut(|ledger| ledger.timestamp += 12 * 60 * 60);
+    }
+    assert_eq!(client.get_spent(&token), 100);
+    env.ledger().with_mut(|ledger| ledger.timestamp += 60 * 60);
+    assert_eq!(client.get_spent(&token), 40);
+    let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 61)];
+    assert_eq!(
+        check_calls(&env, &contract_id, &[&key], calls),
+        Err(Ok(Error::DailyLimitExceeded))
+    );
+    let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 60)];
+    assert_eq!(check_calls(&env, &contract_id, &[&key], calls), Ok(()));
+}
+
+#[test]
+fn test_daily_spending_is_bucketed() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let token = Address::generate(&env);
+    let spend_limit = SpendLimit {
+        limit: 1_000,
+        override_weight: 2,
+    };
+    client.set_spend_limit(&token, &spend_limit);
+
+    // Small spends every 20 minutes for two days are kept in at most 25 hourly buckets
+    for _ in 0..(2 * 24 * 3) {
+        // Each authorization gets its own transaction's budget
+        env.budget().reset_default();
+        let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 1)];
+        assert_eq!(check_calls(&env, &contract_id, &[&key], calls), Ok(()));
+        env.ledger().with_mut(|ledger| ledger.timestamp += 20 * 60);
+    }
+    let stored: Vec<SpendBucket> = env.as_contract(&contract_id, || {
+        env.storage()
+            .persistent()
+            .get(&DataKey::Spending(token.clone()))
+            .unwrap()
+    });
+    assert_eq!(stored.len(), 25);
+    assert_eq!(client.get_spent(&token), 24 * 3);
+}
+
+#[test]
+fn test_daily_spend_limit_applies_to_session_keys() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let token = Address::generate(&env);
+    client.set_spend_limit(
+        &token,
+        &SpendLimit {
+            limit: 100,
+            override_weight: 1,
+        },
+    );
+
+    // A leaked session key without its own cap still cannot drain the account
+    let session = ed25519_session_key(1);
+    let allowed_calls = soroban_sdk::vec![
+        &env,
+        AllowedCall {
+            contract: token.clone(),
+            functions: Vec::new(&env),
+        }
+    ];
+    client.add_session_key(
+        &ed25519_session_public_key(&env, &session),
+        &(env.ledger().sequence() + 100),
+        &allowed_calls,
+        &Map::new(&env),
+    );
+    for (amount, expected) in [(80, Ok(())), (30, Err(Ok(Error::DailyLimitExceeded)))] {
+        let payload = BytesN::random(&env);
+        let authorization = sign_ed25519_session(&env, &session, &payload);
+        let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, amount)];
+        assert_eq!(
+            check_auth_for(&env, &contract_id, &payload, authorization, calls),
+            expected
+        );
+    }
+
+    for spend_limit in [
+        SpendLimit {
+            limit: -1,
+            override_weight: 1,
+        },
+        SpendLimit {
+            limit: 100,
+            override_weight: 0,
+        },
+    ] {
+        let result = client.try_set_spend_limit(&token, &spend_limit);
+        assert_eq!(result, Err(Ok(Error::InvalidSpendLimit)));
+    }
+}
+
+/// Records `account` as an installer of the calling mock policy
+fn record_installer(env: &Env, account: &Address) {
+    account.require_auth();
+    env.storage().persistent().set(account, &());
+}
+
+/// Fails unless `account` is calling and has installed the calling mock policy
+fn require_installer(env: &Env, account: &Address) {
+    account.require_auth();
+    assert!(env.storage().persistent().has(account), "not installed");
+}
+
+/// Mock policy contracts, in their own modules so their generated items do not clash
+mod recording_policy {
+    use super::*;
+
+    /// Mock policy that records what it was asked to authorize
+    #[contract]
+    pub struct RecordingPolicy;
+
+    #[contractimpl]
+    impl RecordingPolicy {
+        pub fn install(env: Env, account: Address) {
+            record_installer(&env, &account);
+        }
+
+        pub fn enforce(
+            env: Env,
+            account: Address,
+            authorizer: Authorizer,
+            auth_contexts: Vec<Context>,
+        ) {
+            require_installer(&env, &account);
+            env.storage().instance().set(
+                &soroban_sdk::symbol_short!("last"),
+                &(account, authorizer, auth_contexts.len()),
+            );
+        }
+
+        pub fn last(env: Env) -> (Address, Authorizer, u32) {
+            env.storage()
+                .instance()
+                .get(&soroban_sdk::symbol_short!("last"))
+                .unwrap()
+        }
+    }
+}
+
+mod allow_list_policy {
+    use super::*;
+
+    /// Mock policy that only allows calls to one contract
+    #[contract]
+    pub struct AllowListPolicy;
+
+    #[contractimpl]
+    impl AllowListPolicy {
+        pub fn allow(env: Env, contract: Address) {
+            env.storage()
+                .instance()
+                .set(&soroban_sdk::symbol_short!("allowed"), &contract);
+        }
+
+        pub fn install(env: Env, account: Address) {
+            record_installer(&env, &account);
+        }
+
+        pub fn enforce(
+            env: Env,
+            account: Address,
+            _authorizer: Authorizer,
+            auth_contexts: Vec<Context>,
+        ) {
+            require_installer(&env, &account);
+            let allowed: Address = env
+                .storage()
+                .instance()
+                .get(&soroban_sdk::symbol_short!("allowed"))
+                .unwrap();
+            for context in auth_contexts.iter() {
+                match context {
+                    Context::Contract(call) if call.contract == allowed => {}
+                    _ => panic!("call not allowed"),
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn test_policy_contracts() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let recording = env.register_contract(None, recording_policy::RecordingPolicy);
+    let allow_list = env.register_contract(None, allow_list_policy::AllowListPolicy);
+    let token = Address::generate(&env);
+    allow_list_policy::AllowListPolicyClient::new(&env, &allow_list).allow(&token);
+
+    client.install_policy(&recording);
+    assert_eq!(
+        env.auths()[0].0,
+        contract_id,
+        "installing a policy requires the account's own auth"
+    );
+    client.install_policy(&allow_list);
+    assert_eq!(
+        client.list_policies(),
+        soroban_sdk::vec![&env, recording.clone(), allow_list.clone()]
+    );
+    assert_eq!(
+        client.try_install_policy(&recording),
+        Err(Ok(Error::PolicyAlreadyInstalled))
+    );
+
+    // Policies see who signed and what is being authorized
+    let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 1)];
+    assert_eq!(check_calls(&env, &contract_id, &[&key], calls), Ok(()));
+    assert_eq!(
+        recording_policy::RecordingPolicyClient::new(&env, &recording).last(),
+        (
+            contract_id.clone(),
+            Authorizer::Passkeys(soroban_sdk::vec![&env, credential_id(&env, &key)]),
+            1
+        )
+    );
+
+    // Any policy can reject
+    let calls = soroban_sdk::vec![
+        &env,
+        contract_call(&env, &contract_id, "set_threshold", Vec::new(&env))
+    ];
+    assert_eq!(
+        check_calls(&env, &contract_id, &[&key], calls),
+        Err(Ok(Error::PolicyRejected))
+    );
+
+    // Uninstalling is never blocked by a policy
+    let calls = soroban_sdk::vec![
+        &env,
+        contract_call(&env, &contract_id, "uninstall_policy", Vec::new(&env))
+    ];
+    assert_eq!(check_calls(&env, &contract_id, &[&key], calls), Ok(()));
+    client.uninstall_policy(&allow_list);
+    assert_eq!(client.list_policies(), soroban_sdk::vec![&env, recording]);
+    let calls = soroban_sdk::vec![
+        &env,
+        contract_call(&env, &contract_id, "set_threshold", Vec::new(&env))
+    ];
+    assert_eq!(check_calls(&env, &contract_id, &[&key], calls), Ok(()));
+    assert_eq!(
+        client.try_uninstall_policy(&allow_list),
+        Err(Ok(Error::PolicyNotInstalled))
+    );
+}
+
+#[test]
+fn test_policy_contracts_only_enforce_for_installers() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let recording = env.register_contract(None, recording_policy::RecordingPolicy);
+    client.install_policy(&recording);
+    env.set_auths(&[]);
+
+    // The account itself gets its authorizations checked...
+    let token = Address::generate(&env);
+    let calls = soroban_sdk::vec![&env, transfer(&env, &token, &contract_id, 1)];
+    assert_eq!(
+        check_calls(&env, &contract_id, &[&key], calls.clone()),
+        Ok(())
+    );
+
+    // ...but no one else can drive the policy on its behalf
+    let policy = recording_policy::RecordingPolicyClient::new(&env, &recording);
+    let authorizer = Authorizer::Passkeys(Vec::new(&env));
+    assert!(policy
+        .try_enforce(&contract_id, &authorizer, &calls)
+        .is_err());
+
+    // Nor for an account that never installed it, even with that account's auth
+    let other = setup(&env, &signing_key(8));
+    env.mock_all_auths();
+    assert!(policy.try_enforce(&other, &authorizer, &calls).is_err());
+}
+
+#[test]
+fn test_policy_contracts_see_session_keys() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let recording = env.register_contract(None, recording_policy::RecordingPolicy);
+    client.install_policy(&recording);
+
+    let game = Address::generate(&env);
+    let session = ed25519_session_key(1);
+    let public_key = ed25519_session_public_key(&env, &session);
+    let allowed_calls = soroban_sdk::vec![
+        &env,
+        AllowedCall {
+            contract: game.clone(),
+            functions: Vec::new(&env),
+        }
+    ];
+    client.add_session_key(
+        &public_key,
+        &(env.ledger().sequence() + 100),
+        &allowed_calls,
+        &Map::new(&env),
+    );
+
+    let payload = BytesN::random(&env);
+    let authorization = sign_ed25519_session(&env, &session, &payload);
+    let calls = soroban_sdk::vec![&env, contract_call(&env, &game, "move", Vec::new(&env))];
+    assert_eq!(
+        check_auth_for(&env, &contract_id, &payload, authorization, calls),
+        Ok(())
+    );
+    assert_eq!(
+        recording_policy::RecordingPolicyClient::new(&env, &recording).last(),
+        (contract_id, Authorizer::SessionKey(public_key), 1)
+    );
+}
+
+#[test]
+fn test_version_and_migrate() {
+    let env = Env::default();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    assert_eq!(client.version(), VERSION);
+    client.migrate();
+    assert_eq!(client.version(), VERSION);
+
+    // Accounts created before versioning are brought up to date
+    env.as_contract(&contract_id, || {
+        env.storage().instance().remove(&DataKey::Version);
+    });
+    assert_eq!(client.version(), 0);
+    client.migrate();
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "migrated"),),
+        (0u32, VERSION),
+    );
+    assert_eq!(client.version(), VERSION);
+
+    // Data from newer code cannot be migrated back
+    env.as_contract(&contract_id, || {
+        env.storage()
+            .instance()
+            .set(&DataKey::Version, &(VERSION + 1));
+    });
+    assert_eq!(client.try_migrate(), Err(Ok(Error::InvalidVersion)));
+}
+
+#[test]
+fn test_upgrade() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let wasm_hash = env.deployer().upload_contract_wasm([0u8; 0].as_slice());
+
+    // Without a delay the upgrade is immediate
+    client.upgrade(&wasm_hash);
+    assert_eq!(
+        env.auths()[0].0,
+        contract_id,
+        "upgrading requires the account's own auth"
+    );
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "upgraded"),),
+        wasm_hash,
+    );
+}
+
+#[test]
+fn test_upgrade_timelock() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let wasm_hash = env.deployer().upload_contract_wasm([0u8; 0].as_slice());
+    let delay = 72 * 60 * 60;
+    client.set_upgrade_delay(&delay);
+    assert_eq!(client.get_upgrade_delay(), delay);
+    assert_eq!(
+        client.try_set_upgrade_delay(&(delay - 1)),
+        Err(Ok(Error::InvalidUpgradeDelay))
+    );
+
+    // The upgrade is only scheduled...
+    client.upgrade(&wasm_hash);
+    let execute_after = env.ledger().timestamp() + delay;
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "upgrade_scheduled"),),
+        (wasm_hash.clone(), execute_after),
+    );
+    assert_eq!(
+        client.get_pending_upgrade(),
+        Some(PendingUpgrade {
+            wasm_hash: wasm_hash.clone(),
+            execute_after,
+        })
+    );
+    assert_eq!(
+        client.try_upgrade(&wasm_hash),
+        Err(Ok(Error::UpgradeInProgress))
+    );
+    assert_eq!(client.try_apply_upgrade(), Err(Ok(Error::UpgradeNotReady)));
+
+    // ...and can be applied by anyone once the delay has passed
+    env.set_auths(&[]);
+    env.ledger().with_mut(|ledger| ledger.timestamp += delay);
+    client.apply_upgrade();
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "upgraded"),),
+        wasm_hash,
+    );
+}
+
+#[test]
+fn test_cancel_upgrade() {
+    let env = Env::default();
+    let keys = [signing_key(7), signing_key(8), signing_key(9)];
+    let contract_id = setup_two_of_three(&env, &keys);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let wasm_hash = env.deployer().upload_contract_wasm([0u8; 0].as_slice());
+    client.set_upgrade_delay(&60);
+    client.upgrade(&wasm_hash);
+
+    // A single signer of the 2-of-3 account can stop an upgrade it did not expect
+    let calls = soroban_sdk::vec![
+        &env,
+        contract_call(&env, &contract_id, "cancel_upgrade", Vec::new(&env))
+    ];
+    assert_eq!(check_calls(&env, &contract_id, &[&keys[2]], calls), Ok(()));
+
+    client.cancel_upgrade();
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "upgrade_cancelled"),),
+        wasm_hash,
+    );
+    assert_eq!(client.get_pending_upgrade(), None);
+    assert_eq!(client.try_apply_upgrade(), Err(Ok(Error::NoUpgradePending)));
+    assert_eq!(
+        client.try_cancel_upgrade(),
+        Err(Ok(Error::NoUpgradePending))
+    );
+}
+
+/// Remaining TTL of the account's instance, in ledgers
+fn instance_ttl(env: &Env, contract_id: &Address) -> u32 {
+    env.as_contract(contract_id, || env.storage().instance().get_ttl())
+}
+
+/// Closes `days` worth of ledgers
+fn advance_days(env: &Env, days: u32) {
+    env.ledger()
+        .with_mut(|ledger| ledger.sequence_number += days * DAY_IN_LEDGERS);
+}
+
+#[test]
+fn test_ttl_extended_by_use() {
+    let env = Env::default();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    assert_eq!(client.get_ttl_config(), DEFAULT_TTL_CONFIG);
+    assert_eq!(instance_ttl(&env, &contract_id), 90 * DAY_IN_LEDGERS);
+
+    // Above the threshold, the TTL is left alone
+    advance_days(&env, 10);
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &key, &payload);
+    assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+    assert_eq!(instance_ttl(&env, &contract_id), 80 * DAY_IN_LEDGERS);
+
+    // An account used every couple of months stays alive for years
+    for _ in 0..20 {
+        advance_days(&env, 70);
+        let payload = BytesN::random(&env);
+        let signature = sign(&env, &key, &payload);
+        assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+        assert_eq!(instance_ttl(&env, &contract_id), 90 * DAY_IN_LEDGERS);
+    }
+    assert_eq!(
+        client.list_signers(),
+        soroban_sdk::vec![&env, credential_id(&env, &key)]
+    );
+}
+
+#[test]
+fn test_ttl_extended_by_admin_calls() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+
+    advance_days(&env, 70);
+    client.add_allowed_origin(&Bytes::from_slice(&env, b"https://app.example"));
+    assert_eq!(instance_ttl(&env, &contract_id), 90 * DAY_IN_LEDGERS);
+
+    // Anyone can keep an unused account alive
+    advance_days(&env, 70);
+    env.set_auths(&[]);
+    client.extend_ttl();
+    assert_eq!(instance_ttl(&env, &contract_id), 90 * DAY_IN_LEDGERS);
+}
+
+#[test]
+fn test_set_ttl_config() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+
+    let config = TtlConfig {
+        threshold: 100 * DAY_IN_LEDGERS,
+        extend_to: 200 * DAY_IN_LEDGERS,
+    };
+    client.set_ttl_config(&config);
+    assert_eq!(
+        env.auths()[0].0,
+        contract_id,
+        "changing the TTL configuration requires the account's own auth"
+    );
+    assert_eq!(client.get_ttl_config(), config);
+    assert_eq!(instance_ttl(&env, &contract_id), 200 * DAY_IN_LEDGERS);
+
+    // A shorter extension applies once the TTL falls below the new threshold
+    advance_days(&env, 150);
+    client.extend_ttl();
+    assert_eq!(instance_ttl(&env, &contract_id), 200 * DAY_IN_LEDGERS);
+
+    let max_ttl = env.as_contract(&contract_id, || env.storage().max_ttl());
+    for config in [
+        TtlConfig {
+            threshold: 2 * DAY_IN_LEDGERS,
+            extend_to: DAY_IN_LEDGERS,
+        },
+        TtlConfig {
+            threshold: DAY_IN_LEDGERS,
+            extend_to: max_ttl + 1,
+        },
+    ] {
+        let result = client.try_set_ttl_config(&config);
+        assert_eq!(result, Err(Ok(Error::InvalidTtlConfig)));
+    }
+}
+
+#[test]
+fn test_signer_events() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let topic = |name: &str| Symbol::new(&env, name);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (topic("initialized"), credential_id(&env, &key)),
+        public_key(&env, &key),
+    );
+
+    let phone = signing_key(8);
+    let phone_id = credential_id(&env, &phone);
+    client.add_signer(&phone_id, &sec1_public_key(&env, &phone), &2);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (topic("signer_added"), phone_id.clone()),
+        (public_key(&env, &phone), 2u32),
+    );
+
+    client.set_threshold(&2);
+    assert_last_event(&env, &contract_id, (topic("threshold_changed"),), 2u32);
+
+    let policy = Policy {
+        allowed_calls: Vec::new(&env),
+        max_amount: Some(10),
+    };
+    client.set_signer_policy(&phone_id, &policy);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (topic("signer_policy_set"), phone_id.clone()),
+        policy,
+    );
+    client.remove_signer_policy(&phone_id);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (topic("signer_policy_removed"), phone_id.clone()),
+        (),
+    );
+
+    client.set_threshold(&1);
+    client.remove_signer(&phone_id);
+    assert_last_event(&env, &contract_id, (topic("signer_removed"), phone_id), ());
+
+    let origin = Bytes::from_slice(&env, b"https://app.example");
+    client.add_allowed_origin(&origin);
+    assert_last_event(&env, &contract_id, (topic("origin_added"),), origin.clone());
+    client.remove_allowed_origin(&origin);
+    assert_last_event(&env, &contract_id, (topic("origin_removed"),), origin);
+    client.set_user_verification_required(&false);
+    assert_last_event(&env, &contract_id, (topic("user_verification_set"),), false);
+}
+
+#[test]
+fn test_configuration_events() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let topic = |name: &str| Symbol::new(&env, name);
+
+    let config = recovery_config(&env);
+    client.set_recovery_config(&config);
+    assert_last_event(&env, &contract_id, (topic("recovery_config_set"),), config);
+
+    let session_key = ed25519_session_public_key(&env, &ed25519_session_key(1));
+    let token = Address::generate(&env);
+    let allowed_calls = soroban_sdk::vec![
+        &env,
+        AllowedCall {
+            contract: token.clone(),
+            functions: Vec::new(&env),
+        }
+    ];
+    let spend_limits = soroban_sdk::map![&env, (token, 5i128)];
+    client.add_session_key(&session_key, &100, &allowed_calls, &spend_limits);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (topic("session_key_added"), session_key.clone()),
+        (100u32, spend_limits),
+    );
+    client.revoke_session_key(&session_key);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (topic("session_key_revoked"), session_key),
+        (),
+    );
+
+    let token = Address::generate(&env);
+    let spend_limit = SpendLimit {
+        limit: 100,
+        override_weight: 2,
+    };
+    client.set_spend_limit(&token, &spend_limit);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (topic("spend_limit_set"), token.clone()),
+        spend_limit,
+    );
+    client.remove_spend_limit(&token);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (topic("spend_limit_removed"), token),
+        (),
+    );
+
+    let policy = env.register_contract(None, recording_policy::RecordingPolicy);
+    client.install_policy(&policy);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (topic("policy_installed"), policy.clone()),
+        (),
+    );
+    client.uninstall_policy(&policy);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (topic("policy_uninstalled"), policy),
+        (),
+    );
+
+    client.set_upgrade_delay(&60);
+    assert_last_event(&env, &contract_id, (topic("upgrade_delay_set"),), 60u64);
+
+    let ttl_config = TtlConfig {
+        threshold: DAY_IN_LEDGERS,
+        extend_to: 2 * DAY_IN_LEDGERS,
+    };
+    client.set_ttl_config(&ttl_config);
+    assert_last_event(&env, &contract_id, (topic("ttl_config_set"),), ttl_config);
+}
+
+/// What an account's signers sign to vouch for an off-chain message hash
+fn message_challenge(env: &Env, contract_id: &Address, hash: &BytesN<32>) -> BytesN<32> {
+    let mut message = Bytes::from_slice(env, b"is_valid_signature");
+    message.append(&contract_id.clone().to_xdr(env));
+    message.append(&hash.clone().into());
+    env.crypto().sha256(&message).to_bytes()
+}
+
+/// Passkey authorization of an off-chain message hash for an account
+fn sign_message(
+    env: &Env,
+    contract_id: &Address,
+    keys: &[&SigningKey],
+    hash: &BytesN<32>,
+) -> Authorization {
+    let challenge = message_challenge(env, contract_id, hash);
+    let mut signatures = Vec::new(env);
+    for key in keys {
+        signatures.push_back(sign(env, key, &challenge));
+    }
+    Authorization::Passkeys(signatures)
+}
+
+#[test]
+fn test_is_valid_signature() {
+    let env = Env::default();
+    let keys = [signing_key(1), signing_key(2), signing_key(3)];
+    let contract_id = setup_two_of_three(&env, &keys);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let hash = BytesN::random(&env);
+
+    // Messages need the same signer weight as transactions
+    let both = sign_message(&env, &contract_id, &[&keys[0], &keys[1]], &hash);
+    assert_eq!(client.try_is_valid_signature(&hash, &both), Ok(Ok(())));
+    let one = sign_message(&env, &contract_id, &[&keys[0]], &hash);
+    assert_eq!(
+        client.try_is_valid_signature(&hash, &one),
+        Err(Ok(Error::ThresholdNotMet))
+    );
+
+    // The assertions must be over this exact hash
+    let other = BytesN::random(&env);
+    assert_eq!(
+        client.try_is_valid_signature(&other, &both),
+        Err(Ok(Error::ChallengeMismatch))
+    );
+
+    // ...for this account: another account with the same signers does not accept them
+    let other_account = setup_two_of_three(&env, &keys);
+    assert_eq!(
+        PasskeyAccountClient::new(&env, &other_account).try_is_valid_signature(&hash, &both),
+        Err(Ok(Error::ChallengeMismatch))
+    );
+
+    // ...and not as a transaction authorization with `hash` as its payload
+    let calls = soroban_sdk::vec![
+        &env,
+        contract_call(&env, &contract_id, "is_valid_signature", Vec::new(&env))
+    ];
+    assert_eq!(
+        check_auth_for(&env, &contract_id, &hash, both.clone(), calls),
+        Err(Ok(Error::ChallengeMismatch))
+    );
+
+    // Checking a signature records nothing, so the counter stays usable on-chain
+    let data = authenticator_data_with(&env, b"localhost", 0x05, 9);
+    let challenge = message_challenge(&env, &contract_id, &hash);
+    let signed = |key: &SigningKey| {
+        sign_assertion(
+            &env,
+            key,
+            data.clone(),
+            client_data_json(&challenge).as_bytes(),
+        )
+    };
+    let counted =
+        Authorization::Passkeys(soroban_sdk::vec![&env, signed(&keys[0]), signed(&keys[1])]);
+    let events = env.events().all().len();
+    assert_eq!(client.try_is_valid_signature(&hash, &counted), Ok(Ok(())));
+    assert_eq!(env.events().all().len(), events);
+    assert_eq!(
+        client.get_signer(&credential_id(&env, &keys[0])).sign_count,
+        0
+    );
+}
+
+#[test]
+fn test_is_valid_signature_respects_signer_policies() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let key = signing_key(7);
+    let contract_id = setup(&env, &key);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let hash = BytesN::random(&env);
+    let signature = sign_message(&env, &contract_id, &[&key], &hash);
+
+    // A signer restricted to a token cannot sign messages for the account
+    let token = Address::generate(&env);
+    let mut policy = Policy {
+        allowed_calls: soroban_sdk::vec![
+            &env,
+            AllowedCall {
+                contract: token,
+                functions: Vec::new(&env),
+            }
+        ],
+        max_amount: None,
+    };
+    client.set_signer_policy(&credential_id(&env, &key), &policy);
+    assert_eq!(
+        client.try_is_valid_signature(&hash, &signature),
+        Err(Ok(Error::ContextNotAllowed))
+    );
+
+    // Unless its policy lists message signing
+    policy.allowed_calls.push_back(AllowedCall {
+        contract: contract_id.clone(),
+        functions: soroban_sdk::vec![&env, Symbol::new(&env, "is_valid_signature")],
+    });
+    client.set_signer_policy(&credential_id(&env, &key), &policy);
+    assert_eq!(client.try_is_valid_signature(&hash, &signature), Ok(Ok(())));
+
+    // Policy contracts are not consulted, as they may keep state
+    let allow_list = env.register_contract(None, allow_list_policy::AllowListPolicy);
+    allow_list_policy::AllowListPolicyClient::new(&env, &allow_list)
+        .allow(&Address::generate(&env));
+    client.install_policy(&allow_list);
+    assert_eq!(client.try_is_valid_signature(&hash, &signature), Ok(Ok(())));
+}
+
+#[test]
+fn test_is_valid_signature_session_keys() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let expiration_ledger = env.ledger().sequence() + 100;
+    let hash = BytesN::random(&env);
+    let challenge = message_challenge(&env, &contract_id, &hash);
+
+    // A session key scoped to a token cannot sign messages
+    let session = ed25519_session_key(1);
+    let token_calls = soroban_sdk::vec![
+        &env,
+        AllowedCall {
+            contract: Address::generate(&env),
+            functions: Vec::new(&env),
+        }
+    ];
+    client.add_session_key(
+        &ed25519_session_public_key(&env, &session),
+        &expiration_ledger,
+        &token_calls,
+        &Map::new(&env),
+    );
+    let signature = sign_ed25519_session(&env, &session, &challenge);
+    assert_eq!(
+        client.try_is_valid_signature(&hash, &signature),
+        Err(Ok(Error::SessionKeyOutOfScope))
+    );
+
+    // A key may be allowed to sign messages, but not to call anything else on the account
+    let sign_messages = soroban_sdk::vec![
+        &env,
+        AllowedCall {
+            contract: contract_id.clone(),
+            functions: soroban_sdk::vec![&env, Symbol::new(&env, "is_valid_signature")],
+        }
+    ];
+    let any_call = soroban_sdk::vec![
+        &env,
+        AllowedCall {
+            contract: contract_id.clone(),
+            functions: Vec::new(&env),
+        }
+    ];
+    let session_key = ed25519_session_public_key(&env, &session);
+    assert_eq!(
+        client.try_add_session_key(&session_key, &expiration_ledger, &any_call, &Map::new(&env)),
+        Err(Ok(Error::InvalidSessionKey))
+    );
+    client.add_session_key(
+        &session_key,
+        &expiration_ledger,
+        &sign_messages,
+        &Map::new(&env),
+    );
+    assert_eq!(client.try_is_valid_signature(&hash, &signature), Ok(Ok(())));
+
+    // secp256r1 session keys sign the challenge as a message (ECDSA with SHA-256)
+    let session = signing_key(2);
+    let session_key = SessionPublicKey::Secp256r1(public_key(&env, &session));
+    client.add_session_key(
+        &session_key,
+        &expiration_ledger,
+        &sign_messages,
+        &Map::new(&env),
+    );
+    let signature: p256::ecdsa::Signature =
+        p256::ecdsa::signature::Signer::sign(&session, &challenge.to_array());
+    let signature = signature.normalize_s().unwrap_or(signature);
+    let signature = Authorization::SessionKey(SessionSignature {
+        public_key: session_key,
+        signature: BytesN::from_array(&env, &signature.to_bytes().into()),
+    });
+    assert_eq!(client.try_is_valid_signature(&hash, &signature), Ok(Ok(())));
+}
+
+/// AAGUID of the test authenticator model
+const AAGUID: [u8; 16] = [0xad; 16];
+
+/// DER value, with a short or long-form length
+fn der(tag: u8, contents: &[u8]) -> std::vec::Vec<u8> {
+    let mut der = std::vec![tag];
+    match contents.len() {
+        len @ 0..=0x7f => der.push(len as u8),
+        len @ 0x80..=0xff => der.extend([0x81, len as u8]),
+        len => der.extend([0x82, (len >> 8) as u8, len as u8]),
+    }
+    der.extend_from_slice(contents);
+    der
+}
+
+/// DER `ECDSA-Sig-Value` of an ES256 signature over `message`
+fn der_signature(key: &SigningKey, message: &[u8]) -> std::vec::Vec<u8> {
+    let signature: p256::ecdsa::Signature = p256::ecdsa::signature::Signer::sign(key, message);
+    let mut integers = std::vec::Vec::new();
+    for scalar in [signature.r().to_bytes(), signature.s().to_bytes()] {
+        let start = scalar.iter().position(|b| *b != 0).unwrap();
+        let mut integer = std::vec::Vec::new();
+        if scalar[start] & 0x80 != 0 {
+            integer.push(0);
+        }
+        integer.extend_from_slice(&scalar[start..]);
+        integers.extend(der(0x02, &integer));
+    }
+    der(0x30, &integers)
+}
+
+/// Key the test authenticator model's vendor signs attestation certificates with
+fn attestation_issuer() -> SigningKey {
+    signing_key(21)
+}
+
+/// X.509 attestation certificate for `key` issued by `issuer`, naming `aaguid` in its
+/// FIDO extension
+fn attestation_certificate(
+    key: &SigningKey,
+    issuer: &SigningKey,
+    aaguid: &[u8; 16],
+    ca: bool,
+) -> std::vec::Vec<u8> {
+    let algorithm = der(
+        0x30,
+        &der(0x06, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x04, 0x03, 0x02]),
+    );
+    let common_name = [
+        der(0x06, &[0x55, 0x04, 0x03]),
+        der(0x0c, b"Test Authenticator"),
+    ];
+    let name = der(0x30, &der(0x31, &der(0x30, &common_name.concat())));
+    let validity = [der(0x17, b"240101000000Z"), der(0x17, b"340101000000Z")];
+
+    let point = key.verifying_key().to_encoded_point(false);
+    let ec_public_key = der(0x06, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x02, 0x01]);
+    let p256 = der(0x06, &[0x2a, 0x86, 0x48, 0xce, 0x3d, 0x03, 0x01, 0x07]);
+    let key_info = [
+        der(0x30, &[ec_public_key, p256].concat()),
+        der(0x03, &[&[0x00], point.as_bytes()].concat()),
+    ];
+
+    let constraints = if ca { der(0x01, &[0xff]) } else { std::vec![] };
+    let fido_aaguid = [
+        0x2b, 0x06, 0x01, 0x04, 0x01, 0x82, 0xe5, 0x1c, 0x01, 0x01, 0x04,
+    ];
+    let extensions = [
+        der(
+            0x30,
+            &[
+                der(0x06, &[0x55, 0x1d, 0x13]),
+                der(0x01, &[0xff]),
+                der(0x04, &der(0x30, &constraints)),
+            ]
+            .concat(),
+        ),
+        der(
+            0x30,
+            &[der(0x06, &fido_aaguid), der(0x04, &der(0x04, aaguid))].concat(),
+        ),
+    ];
+
+    let tbs = der(
+        0x30,
+        &[
+            der(0xa0, &der(0x02, &[0x02])),
+            der(0x02, &[0x01]),
+            algorithm.clone(),
+            name.clone(),
+            der(0x30, &validity.concat()),
+            name,
+            der(0x30, &key_info.concat()),
+            der(0xa3, &der(0x30, &extensions.concat())),
+        ]
+        .concat(),
+    );
+    let signature = der(0x03, &[&[0x00], &der_signature(issuer, &tbs)[..]].concat());
+    der(0x30, &[tbs, algorithm, signature].concat())
+}
+
+/// CBOR header of a byte (major type 2) or text (3) string, or a map (5)
+fn cbor_head(major: u8, len: usize) -> std::vec::Vec<u8> {
+    match len {
+        0..=23 => std::vec![major << 5 | len as u8],
+        24..=0xff => std::vec![major << 5 | 24, len as u8],
+        _ => std::vec![major << 5 | 25, (len >> 8) as u8, len as u8],
+    }
+}
+
+fn cbor_string(major: u8, value: &[u8]) -> std::vec::Vec<u8> {
+    [cbor_head(major, value.len()), value.to_vec()].concat()
+}
+
+/// Authenticator data of a new passkey created by an authenticator model
+fn attested_authenticator_data(
+    env: &Env,
+    key: &SigningKey,
+    aaguid: &[u8; 16],
+) -> std::vec::Vec<u8> {
+    let rp_id_hash = env.crypto().sha256(&rp_id(env)).to_array();
+    let credential_id = key.verifying_key().to_encoded_point(false).as_bytes()[1..17].to_vec();
+
+    // UP, UV and AT flags, zero counter
+    let mut data = [&rp_id_hash[..], &[0x45, 0, 0, 0, 0], aaguid].concat();
+    data.extend([0x00, credential_id.len() as u8]);
+    data.extend(credential_id);
+    data.extend(cose_key(key));
+    data
+}
+
+/// clientDataJSON of a registration
+fn create_client_data_json(env: &Env) -> Bytes {
+    let json = br#"{"type":"webauthn.create","challenge":"dGVzdA","origin":"https://localhost"}"#;
+    Bytes::from_slice(env, json)
+}
+
+/// `packed` attestation object, signed by `signer` and carrying its certificate if given
+fn attestation_object(
+    env: &Env,
+    authenticator_data: &[u8],
+    client_data_json: &Bytes,
+    signer: &SigningKey,
+    certificate: Option<std::vec::Vec<u8>>,
+) -> Bytes {
+    let client_data_hash = env.crypto().sha256(client_data_json).to_array();
+    let signature = der_signature(signer, &[authenticator_data, &client_data_hash].concat());
+
+    let mut statement = cbor_head(5, 2 + usize::from(certificate.is_some()));
+    statement.extend(cbor_string(3, b"alg"));
+    statement.push(0x26);
+    statement.extend(cbor_string(3, b"sig"));
+    statement.extend(cbor_string(2, &signature));
+    if let Some(certificate) = certificate {
+        statement.extend(cbor_string(3, b"x5c"));
+        statement.push(0x81);
+        statement.extend(cbor_string(2, &certificate));
+    }
+
+    let object = [
+        cbor_head(5, 3),
+        cbor_string(3, b"fmt"),
+        cbor_string(3, b"packed"),
+        cbor_string(3, b"attStmt"),
+        statement,
+        cbor_string(3, b"authData"),
+        cbor_string(2, authenticator_data),
+    ];
+    Bytes::from_slice(env, &object.concat())
+}
+
+/// Self attestation of a new passkey made by the test authenticator model
+fn self_attestation(env: &Env, key: &SigningKey) -> Bytes {
+    let data = attested_authenticator_data(env, key, &AAGUID);
+    attestation_object(env, &data, &create_client_data_json(env), key, None)
+}
+
+/// Configuration trusting the test authenticator model, checked by a new verifier
+fn attestation_config(env: &Env, allow_self_attestation: bool) -> AttestationConfig {
+    AttestationConfig {
+        verifier: env.register_contract(None, attestation_verifier::AttestationVerifier),
+        aaguids: soroban_sdk::vec![env, BytesN::from_array(env, &AAGUID)],
+        issuers: soroban_sdk::map![
+            env,
+            (
+                BytesN::from_array(env, &AAGUID),
+                public_key(env, &attestation_issuer())
+            )
+        ],
+        allow_self_attestation,
+    }
+}
+
+#[test]
+fn test_init_attested() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = env.register_contract(None, PasskeyAccount);
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let key = signing_key(7);
+    let config = attestation_config(&env, true);
+
+    // The passkey's credential ID and public key come from the attestation
+    client.init_attested(
+        &self_attestation(&env, &key),
+        &create_client_data_json(&env),
+        &allowed_origins(&env),
+        &rp_id(&env),
+        &true,
+        &config,
+        &BackupPolicy::DeviceBound,
+    );
+    assert_last_event(
+        &env,
+        &contract_id,
+        (Symbol::new(&env, "attestation_config_set"),),
+        config.clone(),
+    );
+    assert_eq!(
+        client.list_signers(),
+        soroban_sdk::vec![&env, credential_id(&env, &key)]
+    );
+    let signer = client.get_signer(&credential_id(&env, &key));
+    assert_eq!(signer.public_key, public_key(&env, &key));
+    assert_eq!(client.get_attestation_config(), Some(config));
+    assert_eq!(
+        client.get_backup_policy(&credential_id(&env, &key)),
+        BackupPolicy::DeviceBound
+    );
+
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &key, &payload);
+    assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+
+    // From then on, passkeys must be attested
+    let phone = signing_key(8);
+    let result = client.try_add_signer(
+        &credential_id(&env, &phone),
+        &sec1_public_key(&env, &phone),
+        &1,
+    );
+    assert_eq!(result, Err(Ok(Error::AttestationRequired)));
+    let result = client.try_init_attested(
+        &self_attestation(&env, &phone),
+        &create_client_data_json(&env),
+        &allowed_origins(&env),
+        &rp_id(&env),
+        &true,
+        &attestation_config(&env, true),
+        &BackupPolicy::Any,
+    );
+    assert_eq!(result, Err(Ok(Error::AlreadyInitialized)));
+}
+
+#[test]
+fn test_add_signer_attested() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let registration = create_client_data_json(&env);
+    let attestation_key = signing_key(20);
+    let phone = signing_key(8);
+
+    // Without a configuration no authenticator is trusted
+    let any = BackupPolicy::Any;
+    let object = self_attestation(&env, &phone);
+    let result = client.try_add_signer_attested(&object, &registration, &1, &any);
+    assert_eq!(result, Err(Ok(Error::AttestationNotAllowed)));
+    let data = attested_authenticator_data(&env, &phone, &AAGUID);
+    let certificate =
+        attestation_certificate(&attestation_key, &attestation_issuer(), &AAGUID, false);
+    let certified = attestation_object(
+        &env,
+        &data,
+        &registration,
+        &attestation_key,
+        Some(certificate),
+    );
+    let result = client.try_add_signer_attested(&certified, &registration, &1, &any);
+    assert_eq!(result, Err(Ok(Error::AttestationNotAllowed)));
+
+    // Self attestation is accepted once allowed
+    client.set_attestation_config(&attestation_config(&env, true));
+    client.add_signer_attested(&object, &registration, &1, &any);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (
+            Symbol::new(&env, "signer_attested"),
+            credential_id(&env, &phone),
+        ),
+        BytesN::from_array(&env, &AAGUID),
+    );
+
+    // Once required, only certified authenticators of the allowed model are
+    client.set_attestation_config(&attestation_config(&env, false));
+    let key = signing_key(9);
+    let object = self_attestation(&env, &key);
+    let result = client.try_add_signer_attested(&object, &registration, &1, &any);
+    assert_eq!(result, Err(Ok(Error::AttestationNotAllowed)));
+
+    let attest = |aaguid: &[u8; 16], certified: &[u8; 16], issuer: &SigningKey, ca: bool| {
+        let data = attested_authenticator_data(&env, &key, aaguid);
+        let certificate = attestation_certificate(&attestation_key, issuer, certified, ca);
+        let object = attestation_object(
+            &env,
+            &data,
+            &registration,
+            &attestation_key,
+            Some(certificate),
+        );
+        client.try_add_signer_attested(&object, &registration, &2, &any)
+    };
+    let issuer = attestation_issuer();
+    let other_model = [0xbe; 16];
+    assert_eq!(
+        attest(&other_model, &other_model, &issuer, false),
+        Err(Ok(Error::AttestationNotAllowed))
+    );
+    // The certificate must vouch for the model the authenticator reports
+    assert_eq!(
+        attest(&AAGUID, &other_model, &issuer, false),
+        Err(Ok(Error::InvalidAttestation))
+    );
+    // and must not be a CA certificate
+    assert_eq!(
+        attest(&AAGUID, &AAGUID, &issuer, true),
+        Err(Ok(Error::InvalidAttestation))
+    );
+    // and must be signed by the issuer trusted for the model
+    assert_eq!(
+        attest(&AAGUID, &AAGUID, &attestation_key, false),
+        Err(Ok(Error::InvalidAttestation))
+    );
+    assert_eq!(attest(&AAGUID, &AAGUID, &issuer, false), Ok(Ok(())));
+
+    let signer = client.get_signer(&credential_id(&env, &key));
+    assert_eq!(signer.public_key, public_key(&env, &key));
+    assert_eq!(signer.weight, 2);
+    let payload = BytesN::random(&env);
+    let signature = sign(&env, &key, &payload);
+    assert_eq!(check_auth(&env, &contract_id, &payload, signature), Ok(()));
+
+    // Assertions cannot stand in for registrations
+    let key = signing_key(10);
+    let data = attested_authenticator_data(&env, &key, &AAGUID);
+    let json = Bytes::from_slice(&env, client_data_json(&BytesN::random(&env)).as_bytes());
+    let certificate = attestation_certificate(&attestation_key, &issuer, &AAGUID, false);
+    let object = attestation_object(&env, &data, &json, &attestation_key, Some(certificate));
+    assert_eq!(
+        client.try_add_signer_attested(&object, &json, &1, &any),
+        Err(Ok(Error::InvalidClientDataType))
+    );
+
+    // Allowing a model is not enough for basic attestation without a trusted issuer
+    let mut config = attestation_config(&env, false);
+    config.issuers = Map::new(&env);
+    client.set_attestation_config(&config);
+    let data = attested_authenticator_data(&env, &key, &AAGUID);
+    let certificate = attestation_certificate(&attestation_key, &issuer, &AAGUID, false);
+    let object = attestation_object(
+        &env,
+        &data,
+        &registration,
+        &attestation_key,
+        Some(certificate),
+    );
+    assert_eq!(
+        client.try_add_signer_attested(&object, &registration, &1, &any),
+        Err(Ok(Error::AttestationNotAllowed))
+    );
+
+    // Without the requirement, plain keys can be added again
+    client.remove_attestation_config();
+    assert_eq!(client.get_attestation_config(), None);
+    client.add_signer(&credential_id(&env, &key), &sec1_public_key(&env, &key), &1);
+}
+
+#[test]
+fn test_add_signer_attested_backup_policy() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let contract_id = setup(&env, &signing_key(7));
+    let client = PasskeyAccountClient::new(&env, &contract_id);
+    let registration = create_client_data_json(&env);
+    client.set_attestation_config(&attestation_config(&env, true));
+
+    // A passkey synced through a cloud keychain (BE and BS set)
+    let synced = signing_key(8);
+    let mut data = attested_authenticator_data(&env, &synced, &AAGUID);
+    data[32] |= 0x18;
+    let object = attestation_object(&env, &data, &registration, &synced, None);
+    let result =
+        client.try_add_signer_attested(&object, &registration, &1, &BackupPolicy::DeviceBound);
+    assert_eq!(result, Err(Ok(Error::BackupStateNotAllowed)));
+    client.add_signer_attested(&object, &registration, &1, &BackupPolicy::Synced);
+    assert_last_event(
+        &env,
+        &contract_id,
+        (
+            Symbol::new(&env, "backup_policy_set"),
+            credential_id(&env, &synced),
+        ),
+        BackupPolicy::Synced,
+    );
+
+    // The policy keeps applying to its assertions
+    let payload = BytesN::random(&env);
+    let data = authenticator_data_with(&env, b"localhost", 0x05, 0);
+    let signature = sign_assertion(&env, &synced, data, client_data_json(&payload).as_bytes());
+    let result = check_auth(&env, &contract_id, &payload, signature);
+    assert_eq!(result, Err(Ok(Error::BackupStateNotAllowed)));
+
+    // A device-bound passkey (BE unset)
+    let device_bound = signing_key(9);
+    let object = self_attestation(&env, &device_bound);
+    let result = client.try_add_signer_attested(&object, &registration, &1, &BackupPolicy::Synced);
+    assert_eq!(result, Err(Ok(Error::BackupStateNotAllowed)));
+    client.add_signer_attested(&object, &registration, &1, &BackupPolicy::DeviceBound);
+    assert_eq!(
+        client.get_backup_policy(&credential_id(&env, &device_bound)),
+        BackupPolicy::DeviceBound
+    );
+
+    // Removing a passkey drops its policy
+    client.remove_signer(&credential_id(&env, &synced));
+    assert_eq!(
+        client.get_backup_policy(&credential_id(&env, &synced)),
+        BackupPolicy::Any
     );
-    
-    // Should fail because no owner is set
-    assert!(result.is_err());
 }
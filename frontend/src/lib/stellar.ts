@@ -105,7 +105,7 @@ export async function deployPasskeyContract(
 export async function initializePasskeyAccount(
   contractId: string,
   publicKey: Uint8Array,
-  credentialId: string | null,
+  credentialId: string,
   sourceKeypair: StellarSdk.Keypair
 ): Promise<boolean> {
   const server = initializeSorobanClient();
@@ -122,9 +122,16 @@ export async function initializePasskeyAccount(
       contract.call(
         "init",
         StellarSdk.xdr.ScVal.scvBytes(Buffer.from(publicKey)),
-        credentialId
-          ? StellarSdk.xdr.ScVal.scvBytes(Buffer.from(credentialId))
-          : StellarSdk.xdr.ScVal.scvVoid()
+        // Signers are keyed by credential ID, so assertions can name their key
+        StellarSdk.xdr.ScVal.scvBytes(Buffer.from(credentialId)),
+        // Only assertions from this origin will be accepted by the account
+        StellarSdk.xdr.ScVal.scvVec([
+          StellarSdk.xdr.ScVal.scvBytes(Buffer.from(window.location.origin)),
+        ]),
+        // Relying party ID the passkey was registered for
+        StellarSdk.xdr.ScVal.scvBytes(Buffer.from(window.location.hostname)),
+        // Require biometric/PIN user verification on every assertion
+        StellarSdk.xdr.ScVal.scvBool(true)
       )
     )
     .setTimeout(30)